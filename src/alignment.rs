@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::assets::*;
+use crate::component::*;
+use crate::AppState;
+
+// Sprites shown at once, which is one hair color of the character pack
+const COLUMNS: usize = 7;
+const PAGE: usize = 14;
+const CELL: f32 = 100.;
+const SCALE: f32 = 0.35;
+// Anchor change with every press
+const NUDGE: f32 = 0.01;
+
+pub fn open_alignment_view(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::F3) {
+        keys.clear_just_pressed(KeyCode::F3);
+        app_state.set(AppState::Alignment).ok();
+    }
+}
+
+pub fn setup_alignment_view(mut commands: Commands, my_assets: Res<MyAssets>) {
+    commands.spawn_bundle(Camera2dBundle::default());
+
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.,
+                    font: my_assets.font_pixel.clone(),
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(AlignmentText);
+}
+
+// Every sprite of one part of the table on the same head, drawn the way customers
+// are, the selected one nudged with WASD. Tab switches parts, arrows select,
+// F5 writes the table back to the assets folder, Escape leaves
+#[allow(clippy::too_many_arguments)]
+pub fn alignment_view(
+    mut commands: Commands,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    my_assets: Res<MyAssets>,
+    mut alignment_tables: ResMut<Assets<AlignmentTable>>,
+    atlases: Res<AtlasRegistry>,
+    faces_metadata: Res<FacesMetadata>,
+    body_metadata: Res<BodyMetadata>,
+    grid: Query<Entity, With<AlignmentGrid>>,
+    mut texts: Query<&mut Text, With<AlignmentText>>,
+    mut ev_alignment_tables: EventReader<AssetEvent<AlignmentTable>>,
+    mut part: Local<usize>,
+    mut selected: Local<usize>,
+    mut drawn: Local<bool>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        keys.clear_just_pressed(KeyCode::Escape);
+        *drawn = false;
+        app_state.set(AppState::MainMenu).ok();
+        return;
+    }
+
+    // Atlases listed in the table, hair and face at least
+    let parts: Vec<&RegisteredAtlas> = match alignment_tables.get(&my_assets.alignment_table) {
+        Some(table) => table
+            .atlases()
+            .filter_map(|name| atlases.get(name))
+            .filter(|atlas| !atlas.is_empty())
+            .collect(),
+        None => return,
+    };
+    if parts.is_empty() {
+        return;
+    }
+
+    let previous = (*part, *selected);
+    if keys.just_pressed(KeyCode::Tab) {
+        *part += 1;
+        *selected = 0;
+    }
+    // Also when the table was reloaded with fewer parts
+    *part %= parts.len();
+    let atlas = parts[*part];
+    let sprites = atlas.len();
+    *selected %= sprites;
+
+    if keys.just_pressed(KeyCode::Right) {
+        *selected = (*selected + 1) % sprites;
+    }
+    if keys.just_pressed(KeyCode::Left) {
+        *selected = (*selected + sprites - 1) % sprites;
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        *selected = (*selected + COLUMNS) % sprites;
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        *selected = (*selected + sprites - (COLUMNS % sprites)) % sprites;
+    }
+
+    let sprite = atlas.sprite_name(*selected).unwrap_or_default().to_string();
+    let mut anchor = alignment_tables
+        .get(&my_assets.alignment_table)
+        .and_then(|table| table.anchor(atlas.name, &sprite))
+        .unwrap_or_default();
+    let mut nudged = false;
+    for (key, nudge) in [
+        (KeyCode::A, Vec2::new(NUDGE, 0.)),
+        (KeyCode::D, Vec2::new(-NUDGE, 0.)),
+        (KeyCode::W, Vec2::new(0., -NUDGE)),
+        (KeyCode::S, Vec2::new(0., NUDGE)),
+    ] {
+        if keys.just_pressed(key) {
+            anchor += nudge;
+            nudged = true;
+        }
+    }
+    // Only when nudged, as it also tells everyone the table changed
+    if nudged {
+        if let Some(table) = alignment_tables.get_mut(&my_assets.alignment_table) {
+            table.set_anchor(atlas.name, &sprite, anchor);
+        }
+    }
+
+    let table = match alignment_tables.get(&my_assets.alignment_table) {
+        Some(table) => table,
+        None => return,
+    };
+
+    if keys.just_pressed(KeyCode::F5) {
+        match table.store() {
+            Ok(path) => info!("Stored the alignment table in {}", path.display()),
+            Err(err) => warn!("Failed to store the alignment table: {}", err),
+        }
+    }
+
+    // Also when the table was reloaded from disk
+    if *drawn
+        && !nudged
+        && previous == (*part, *selected)
+        && ev_alignment_tables.iter().count() == 0
+    {
+        return;
+    }
+    *drawn = true;
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "{}: {} ({}/{})\nAnchor: {:.2}, {:.2}\nTab: part, arrows: select, WASD: move, F5: store, Escape: back",
+            atlas.name,
+            sprite,
+            *selected + 1,
+            sprites,
+            anchor.x,
+            anchor.y
+        );
+    }
+
+    for entity in grid.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // Same as `Alignment::anchor`, which can't borrow the table tuned here
+    let anchor_of = |atlas: &RegisteredAtlas, index: usize| {
+        atlas
+            .sprite_name(index)
+            .and_then(|sprite| table.anchor(atlas.name, sprite))
+            .map_or(Anchor::Center, Anchor::Custom)
+    };
+    let head = body_metadata.tints.first().map_or(0, |tint| tint.head);
+    let face = faces_metadata.face_indexes.first().copied().unwrap_or(0);
+    let first = *selected / PAGE * PAGE;
+
+    for index in first..(first + PAGE).min(sprites) {
+        let slot = index - first;
+        let position = Vec3::new(
+            (slot % COLUMNS) as f32 * CELL - CELL * (COLUMNS - 1) as f32 / 2.,
+            -((slot / COLUMNS) as f32) * CELL * 1.5 - 20.,
+            0.,
+        );
+
+        // The sprite takes the place of its part on the head, or goes on top of it
+        let mut layers = vec![
+            (&atlases.skin, head),
+            (&atlases.face, face),
+            (&atlases.hair, 0),
+        ];
+        match layers
+            .iter_mut()
+            .find(|(layer, _)| layer.name == atlas.name)
+        {
+            Some(layer) => layer.1 = index,
+            None => layers.push((atlas, index)),
+        }
+        let head_color = if index == *selected {
+            Color::rgb(1., 0.7, 0.7)
+        } else {
+            Color::WHITE
+        };
+
+        commands
+            .spawn_bundle(SpatialBundle::from_transform(
+                Transform::from_translation(position).with_scale(Vec3::splat(SCALE)),
+            ))
+            .insert(AlignmentGrid)
+            .with_children(|parent| {
+                for (z, (layer, layer_index)) in layers.into_iter().enumerate() {
+                    if layer_index >= layer.len() {
+                        continue;
+                    }
+                    parent.spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: layer.handle.clone(),
+                        transform: Transform::from_xyz(0., 0., z as f32),
+                        sprite: TextureAtlasSprite {
+                            index: layer_index,
+                            anchor: anchor_of(layer, layer_index),
+                            color: if z == 0 { head_color } else { Color::WHITE },
+                            ..default()
+                        },
+                        ..default()
+                    });
+                }
+            });
+    }
+}
@@ -1,5 +1,20 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::ecs::system::SystemParam;
+use bevy::utils::BoxedFuture;
 use bevy::{prelude::*, sprite::Anchor};
 use bevy_asset_loader::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::component::{Appearance, Body, Emo, Ingredient, Ingredients, Layer};
+use crate::system::LAYER_KEYS;
+use crate::taste::Hint;
+use crate::AppState;
 
 #[derive(AssetCollection)]
 pub struct MyAssets {
@@ -13,204 +28,948 @@ pub struct MyAssets {
     pub ui_button_rectangle_wood: Handle<Image>,
     #[asset(path = "Donuts/Spritesheet/donuts_sheet.png")]
     pub donuts_texture: Handle<Image>,
-    #[asset(path = "Donuts/Spritesheet/donuts_sheet.atlas.json")]
+    #[asset(path = "Donuts/Spritesheet/donuts_sheet.xml")]
     pub donuts_texture_data: Handle<TextureAtlasData>,
+    #[asset(path = "Donuts/ingredients.catalog.json")]
+    pub ingredient_catalog: Handle<IngredientCatalog>,
     #[asset(path = "Emote Pack/Spritesheets/vector_style1.png")]
     pub emotes_texture: Handle<Image>,
-    #[asset(path = "Emote Pack/Spritesheets/vector_style1.atlas.json")]
+    #[asset(path = "Emote Pack/Spritesheets/vector_style1.xml")]
     pub emotes_texture_data: Handle<TextureAtlasData>,
     #[asset(path = "Character Pack/Spritesheet/sheet_face.png")]
     pub face_texture: Handle<Image>,
-    #[asset(path = "Character Pack/Spritesheet/sheet_face.atlas.json")]
+    #[asset(path = "Character Pack/Spritesheet/sheet_face.xml")]
     pub face_texture_data: Handle<TextureAtlasData>,
     #[asset(path = "Character Pack/Spritesheet/sheet_hair.png")]
     pub hair_texture: Handle<Image>,
-    #[asset(path = "Character Pack/Spritesheet/sheet_hair.atlas.json")]
+    #[asset(path = "Character Pack/Spritesheet/sheet_hair.xml")]
     pub hair_texture_data: Handle<TextureAtlasData>,
-    // #[asset(path = "Character Pack/Spritesheet/sheet_pants.png")]
-    // pub pants_texture: Handle<Image>,
-    // #[asset(path = "Character Pack/Spritesheet/sheet_pants.atlas.json")]
-    // pub pants_texture_data: Handle<TextureAtlasData>,
-    // #[asset(path = "Character Pack/Spritesheet/sheet_shirts.png")]
-    // pub shirts_texture: Handle<Image>,
-    // #[asset(path = "Character Pack/Spritesheet/sheet_shirts.atlas.json")]
-    // pub shirts_texture_data: Handle<TextureAtlasData>,
-    // #[asset(path = "Character Pack/Spritesheet/sheet_shoes.png")]
-    // pub shoes_texture: Handle<Image>,
-    // #[asset(path = "Character Pack/Spritesheet/sheet_shoes.atlas.json")]
-    // pub shoes_texture_data: Handle<TextureAtlasData>,
+    #[asset(path = "Character Pack/Spritesheet/sheet_pants.png")]
+    pub pants_texture: Handle<Image>,
+    #[asset(path = "Character Pack/Spritesheet/sheet_pants.xml")]
+    pub pants_texture_data: Handle<TextureAtlasData>,
+    #[asset(path = "Character Pack/Spritesheet/sheet_shirts.png")]
+    pub shirts_texture: Handle<Image>,
+    #[asset(path = "Character Pack/Spritesheet/sheet_shirts.xml")]
+    pub shirts_texture_data: Handle<TextureAtlasData>,
+    #[asset(path = "Character Pack/Spritesheet/sheet_shoes.png")]
+    pub shoes_texture: Handle<Image>,
+    #[asset(path = "Character Pack/Spritesheet/sheet_shoes.xml")]
+    pub shoes_texture_data: Handle<TextureAtlasData>,
     #[asset(path = "Character Pack/Spritesheet/sheet_skin.png")]
     pub skin_texture: Handle<Image>,
-    #[asset(path = "Character Pack/Spritesheet/sheet_skin.atlas.json")]
+    #[asset(path = "Character Pack/Spritesheet/sheet_skin.xml")]
     pub skin_texture_data: Handle<TextureAtlasData>,
+    #[asset(path = "Character Pack/alignment.json")]
+    pub alignment_table: Handle<AlignmentTable>,
+}
+
+impl MyAssets {
+    // Image and sprites of every atlas, by their name in the registry
+    fn atlas_sources(&self) -> [(&'static str, &Handle<Image>, &Handle<TextureAtlasData>); 8] {
+        [
+            ("donuts", &self.donuts_texture, &self.donuts_texture_data),
+            ("emotes", &self.emotes_texture, &self.emotes_texture_data),
+            ("face", &self.face_texture, &self.face_texture_data),
+            ("hair", &self.hair_texture, &self.hair_texture_data),
+            ("skin", &self.skin_texture, &self.skin_texture_data),
+            ("shirts", &self.shirts_texture, &self.shirts_texture_data),
+            ("pants", &self.pants_texture, &self.pants_texture_data),
+            ("shoes", &self.shoes_texture, &self.shoes_texture_data),
+        ]
+    }
 }
 
-pub struct Atlases {
-    pub donuts_atlas: Handle<TextureAtlas>,
-    pub emotes_atlas: Handle<TextureAtlas>,
-    pub face_atlas: Handle<TextureAtlas>,
-    pub hair_atlas: Handle<TextureAtlas>,
-    pub skin_atlas: Handle<TextureAtlas>,
+// Every texture atlas of the game, with its sprites by name
+pub struct AtlasRegistry {
+    pub donuts: RegisteredAtlas,
+    pub emotes: RegisteredAtlas,
+    pub face: RegisteredAtlas,
+    pub hair: RegisteredAtlas,
+    pub skin: RegisteredAtlas,
+    pub shirts: RegisteredAtlas,
+    pub pants: RegisteredAtlas,
+    pub shoes: RegisteredAtlas,
 }
 
+impl AtlasRegistry {
+    pub fn get(&self, name: &str) -> Option<&RegisteredAtlas> {
+        [
+            &self.donuts,
+            &self.emotes,
+            &self.face,
+            &self.hair,
+            &self.skin,
+            &self.shirts,
+            &self.pants,
+            &self.shoes,
+        ]
+        .into_iter()
+        .find(|atlas| atlas.name == name)
+    }
+
+    // Atlas sprites are drawn from
+    fn find(&self, handle: &Handle<TextureAtlas>) -> Option<&RegisteredAtlas> {
+        [
+            &self.donuts,
+            &self.emotes,
+            &self.face,
+            &self.hair,
+            &self.skin,
+            &self.shirts,
+            &self.pants,
+            &self.shoes,
+        ]
+        .into_iter()
+        .find(|atlas| atlas.handle == *handle)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut RegisteredAtlas> {
+        [
+            &mut self.donuts,
+            &mut self.emotes,
+            &mut self.face,
+            &mut self.hair,
+            &mut self.skin,
+            &mut self.shirts,
+            &mut self.pants,
+            &mut self.shoes,
+        ]
+        .into_iter()
+        .find(|atlas| atlas.name == name)
+    }
+}
+
+// Texture atlas as big as its image, and where its sprites are in it
+pub struct RegisteredAtlas {
+    // Same as in the alignment table
+    pub name: &'static str,
+    pub handle: Handle<TextureAtlas>,
+    indices: HashMap<String, usize>,
+    sprite_names: Vec<String>,
+}
+
+impl RegisteredAtlas {
+    fn new(
+        name: &'static str,
+        texture: &Handle<Image>,
+        texture_atlas_data: Option<&TextureAtlasData>,
+        images: &Assets<Image>,
+        texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Self {
+        let mut registered_atlas = Self {
+            name,
+            handle: texture_atlases.add(TextureAtlas::new_empty(texture.clone(), Vec2::ZERO)),
+            indices: HashMap::new(),
+            sprite_names: vec![],
+        };
+        registered_atlas.rebuild(texture, texture_atlas_data, images, texture_atlases);
+        registered_atlas
+    }
+
+    // Keeps the handle, so that sprites already drawn from the atlas follow
+    fn rebuild(
+        &mut self,
+        texture: &Handle<Image>,
+        texture_atlas_data: Option<&TextureAtlasData>,
+        images: &Assets<Image>,
+        texture_atlases: &mut Assets<TextureAtlas>,
+    ) {
+        let size = images
+            .get(texture)
+            .map(|image| image.size())
+            .unwrap_or_default();
+        let mut texture_atlas = TextureAtlas::new_empty(texture.clone(), size);
+        texture_atlas.fill_textures(texture_atlas_data);
+        texture_atlases.set_untracked(&self.handle, texture_atlas);
+
+        self.indices = texture_atlas_data
+            .map(|texture_atlas_data| texture_atlas_data.indices.clone())
+            .unwrap_or_default();
+        self.sprite_names = texture_atlas_data
+            .map(|texture_atlas_data| {
+                texture_atlas_data
+                    .sub_textures
+                    .iter()
+                    .map(|sub_texture| sub_texture.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    // Index of every sprite in the given sprites of the atlas, found by name
+    fn remap(&self, texture_atlas_data: &TextureAtlasData) -> Vec<Option<usize>> {
+        self.sprite_names
+            .iter()
+            .map(|name| texture_atlas_data.index_of(name))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprite_names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprite_names.is_empty()
+    }
+
+    pub fn sprite_name(&self, index: usize) -> Option<&str> {
+        self.sprite_names.get(index).map(String::as_str)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    // Falls back to the first sprite, missing ones being reported by `validate`
+    pub fn sprite(&self, name: &str) -> TextureAtlasSprite {
+        TextureAtlasSprite::new(self.index_of(name).unwrap_or_default())
+    }
+}
+
+#[derive(Default)]
 pub struct FacesMetadata {
     pub face_indexes: Vec<usize>,
 }
 
+impl FacesMetadata {
+    pub fn new(face_texture_data: &TextureAtlasData) -> Self {
+        Self {
+            face_indexes: face_texture_data
+                .sub_textures
+                .iter()
+                .enumerate()
+                .filter_map(|(index, sub_texture)| {
+                    if sub_texture.name.starts_with("face") {
+                        Some(index)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct HairMetadata {
     pub names: Vec<String>,
 }
 
 impl HairMetadata {
-    pub fn anchor(&self, index: usize) -> Anchor {
-        if index < self.names.len() {
-            let name = &self.names[index];
+    pub fn new(hair_texture_data: &TextureAtlasData) -> Self {
+        Self {
+            names: hair_texture_data
+                .sub_textures
+                .iter()
+                .map(|sub_texture| sub_texture.name.to_string())
+                .collect(),
+        }
+    }
+}
 
-            println!("{}", name);
-            if name.contains("Man1") {
-                return Anchor::Custom(Vec2::new(0., -0.4));
-            }
-            if name.contains("Man2") {
-                return Anchor::Custom(Vec2::new(0., -0.4));
-            }
-            if name.contains("Man3") {
-                return Anchor::Custom(Vec2::new(0., -0.4));
-            }
-            if name.contains("Man4") {
-                return Anchor::Custom(Vec2::new(0., -0.4));
-            }
-            if name.contains("Man5") {
-                return Anchor::Custom(Vec2::new(-0.05, -0.4));
-            }
-            if name.contains("Man6") {
-                return Anchor::Custom(Vec2::new(0., -0.4));
-            }
-            if name.contains("Man7") {
-                return Anchor::Custom(Vec2::new(0., -0.4));
-            }
-            if name.contains("Man8") {
-                return Anchor::Custom(Vec2::new(0., -0.25));
-            }
+// Sprites of one skin tint, in the skin atlas
+pub struct SkinTint {
+    pub head: usize,
+    pub neck: usize,
+    pub arm: usize,
+    pub hand: usize,
+}
 
-            if name.contains("Woman1") {
-                return Anchor::Custom(Vec2::new(0., 0.06));
-            }
-            if name.contains("Woman2") {
-                return Anchor::Custom(Vec2::new(0., 0.13));
-            }
-            if name.contains("Woman3") {
-                return Anchor::Custom(Vec2::new(0., 0.1));
-            }
-            if name.contains("Woman4") {
-                return Anchor::Custom(Vec2::new(0., 0.14));
-            }
-            if name.contains("Woman5") {
-                return Anchor::Custom(Vec2::new(0., 0.05));
-            }
-            if name.contains("Woman6") {
-                return Anchor::Custom(Vec2::new(0., 0.05));
-            }
+// Torso in the shirts atlas, with the sleeves of the same color
+pub struct Shirt {
+    pub torso: usize,
+    pub sleeves: Vec<usize>,
+}
 
-            Anchor::Center
-        } else {
-            Anchor::Center
+// Hips in the pants atlas, with the legs of the same color
+pub struct Pants {
+    pub hips: usize,
+    pub legs: Vec<usize>,
+}
+
+// Body parts grouped by their sprite names, so that matching ones are put together
+#[derive(Default)]
+pub struct BodyMetadata {
+    pub tints: Vec<SkinTint>,
+    pub shirts: Vec<Shirt>,
+    pub pants: Vec<Pants>,
+    pub shoes: Vec<usize>,
+}
+
+impl BodyMetadata {
+    pub fn new(
+        skin_texture_data: &TextureAtlasData,
+        shirts_texture_data: &TextureAtlasData,
+        pants_texture_data: &TextureAtlasData,
+        shoes_texture_data: &TextureAtlasData,
+    ) -> Self {
+        // e.g. "tint3_head.png"
+        let tints = skin_texture_data
+            .names()
+            .filter_map(|(_, name)| name.strip_suffix("_head"))
+            .filter_map(|tint| {
+                let part =
+                    |part: &str| skin_texture_data.index_of(&format!("{}_{}.png", tint, part));
+                Some(SkinTint {
+                    head: part("head")?,
+                    neck: part("neck")?,
+                    arm: part("arm")?,
+                    hand: part("hand")?,
+                })
+            })
+            .collect();
+
+        // e.g. "blueShirt3.png" and "blueArm_long.png", or "shirtYellow3.png" and "armYellow_long.png"
+        let shirt_color = |name: &str| {
+            let name = name.to_lowercase();
+            name.contains("shirt").then(|| {
+                name.replace("shirt", "")
+                    .trim_end_matches(|c: char| c.is_ascii_digit())
+                    .to_string()
+            })
+        };
+        let sleeve_color = |name: &str| {
+            let name = name.to_lowercase();
+            let (arm, _length) = name.split_once('_')?;
+            arm.contains("arm").then(|| arm.replace("arm", ""))
+        };
+        let shirts = shirts_texture_data
+            .names()
+            .filter_map(|(torso, name)| {
+                let color = shirt_color(name)?;
+                Some(Shirt {
+                    torso,
+                    sleeves: shirts_texture_data
+                        .names()
+                        .filter(|(_, name)| sleeve_color(name).as_ref() == Some(&color))
+                        .map(|(sleeve, _)| sleeve)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        // e.g. "pantsBlue12.png" for the second hips of "pantsBlue1_long.png",
+        // except that the yellow legs are "legYellow_long.png"
+        let leg_color = |name: &str| {
+            let (leg, _length) = name.split_once('_')?;
+            leg.strip_prefix("pants")
+                .or_else(|| leg.strip_prefix("leg"))
+                .map(str::to_string)
+        };
+        let pants = pants_texture_data
+            .names()
+            .filter_map(|(hips, name)| {
+                let color = name.strip_prefix("pants")?;
+                if color.contains('_') {
+                    return None;
+                }
+                let color = &color[..color.len().saturating_sub(1)];
+                Some(Pants {
+                    hips,
+                    legs: pants_texture_data
+                        .names()
+                        .filter(|(_, name)| leg_color(name).as_deref() == Some(color))
+                        .map(|(leg, _)| leg)
+                        .collect(),
+                })
+            })
+            .filter(|pants| !pants.legs.is_empty())
+            .collect();
+
+        Self {
+            tints,
+            shirts,
+            pants,
+            shoes: shoes_texture_data.names().map(|(shoe, _)| shoe).collect(),
+        }
+    }
+
+    // Head and body of a random customer, none when the sheets are missing
+    pub fn random(&self, rng: &mut impl Rng) -> Option<(usize, Body)> {
+        let tint = self.tints.choose(rng)?;
+        let shirt = self.shirts.choose(rng)?;
+        let pants = self.pants.choose(rng)?;
+
+        Some((
+            tint.head,
+            Body {
+                neck: tint.neck,
+                arm: tint.arm,
+                hand: tint.hand,
+                shirt: shirt.torso,
+                sleeve: shirt.sleeves.choose(rng).copied(),
+                hips: pants.hips,
+                leg: *pants.legs.choose(rng)?,
+                shoe: *self.shoes.choose(rng)?,
+            },
+        ))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BodyPart {
+    Neck,
+    Shirt,
+    Arm,
+    Sleeve,
+    Hand,
+    Hips,
+    Leg,
+    Shoe,
+}
+
+impl BodyPart {
+    pub const ALL: [BodyPart; 8] = [
+        BodyPart::Neck,
+        BodyPart::Shirt,
+        BodyPart::Arm,
+        BodyPart::Sleeve,
+        BodyPart::Hand,
+        BodyPart::Hips,
+        BodyPart::Leg,
+        BodyPart::Shoe,
+    ];
+
+    // Key of the alignment table
+    pub fn name(&self) -> &'static str {
+        match self {
+            BodyPart::Neck => "neck",
+            BodyPart::Shirt => "shirt",
+            BodyPart::Arm => "arm",
+            BodyPart::Sleeve => "sleeve",
+            BodyPart::Hand => "hand",
+            BodyPart::Hips => "hips",
+            BodyPart::Leg => "leg",
+            BodyPart::Shoe => "shoe",
         }
     }
 }
 
+// Where a body part goes, relative to the head
+#[derive(Clone, Copy, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Placement {
+    // Point the part hangs from, from the center of the head
+    joint: [f32; 2],
+    // Unless the sprite has an anchor of its own
+    anchor: [f32; 2],
+    // Behind the head, which is at 0
+    pub z: f32,
+    // Also drawn mirrored on the right side, the joint being on the left one
+    #[serde(default)]
+    pub both_sides: bool,
+}
+
+impl Placement {
+    pub fn joint(&self) -> Vec2 {
+        Vec2::from(self.joint)
+    }
+
+    pub fn anchor(&self) -> Anchor {
+        Anchor::Custom(Vec2::from(self.anchor))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn init(
     mut commands: Commands,
     my_assets: Res<MyAssets>,
+    load_errors: Option<Res<AssetErrors>>,
+    images: Res<Assets<Image>>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     texture_atlas_data_assets: Res<Assets<TextureAtlasData>>,
+    ingredient_catalogs: Res<Assets<IngredientCatalog>>,
+    alignment_tables: Res<Assets<AlignmentTable>>,
 ) {
-    let mut donuts_atlas =
-        TextureAtlas::new_empty(my_assets.donuts_texture.clone(), Vec2::new(1024., 2048.));
-    donuts_atlas.fill_textures(texture_atlas_data_assets.get(&my_assets.donuts_texture_data));
-
-    let mut emotes_atlas =
-        TextureAtlas::new_empty(my_assets.emotes_texture.clone(), Vec2::new(1024., 2048.));
-    emotes_atlas.fill_textures(texture_atlas_data_assets.get(&my_assets.emotes_texture_data));
-
-    let mut face_atlas =
-        TextureAtlas::new_empty(my_assets.face_texture.clone(), Vec2::new(256., 512.));
-    face_atlas.fill_textures(texture_atlas_data_assets.get(&my_assets.face_texture_data));
-
-    let faces_metadata = FacesMetadata {
-        face_indexes: texture_atlas_data_assets
-            .get(&my_assets.face_texture_data)
-            .map(|face_texture_data| {
-                face_texture_data
-                    .texture_atlas
-                    .sub_textures
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(index, sub_texture)| {
-                        if sub_texture.name.starts_with("face") {
-                            Some(index)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            })
-            .unwrap_or_else(Vec::new),
+    // Some assets failed to load, nothing to build from
+    if load_errors.is_some() {
+        return;
+    }
+
+    let texture_atlas_data =
+        |handle: &Handle<TextureAtlasData>| texture_atlas_data_assets.get(handle);
+    let mut errors = validate(
+        &my_assets
+            .atlas_sources()
+            .map(|(name, _, data)| (name, texture_atlas_data(data))),
+    );
+
+    let ingredients = match (
+        texture_atlas_data(&my_assets.donuts_texture_data),
+        ingredient_catalogs.get(&my_assets.ingredient_catalog),
+    ) {
+        (Some(donuts_texture_data), Some(catalog)) => catalog
+            .resolve(donuts_texture_data)
+            .unwrap_or_else(|catalog_errors| {
+                errors.extend(catalog_errors);
+                Ingredients::default()
+            }),
+        (_, None) => {
+            errors.push("The ingredient catalog failed to load".to_string());
+            Ingredients::default()
+        }
+        _ => Ingredients::default(),
     };
 
-    let mut hair_atlas =
-        TextureAtlas::new_empty(my_assets.hair_texture.clone(), Vec2::new(2048., 2048.));
-    hair_atlas.fill_textures(texture_atlas_data_assets.get(&my_assets.hair_texture_data));
+    let (faces_metadata, hair_matadata, body_metadata) =
+        character_metadata(&my_assets, &texture_atlas_data_assets);
 
-    let hair_matadata = HairMetadata {
-        names: texture_atlas_data_assets
-            .get(&my_assets.hair_texture_data)
-            .map(|hair_texture_data| {
-                hair_texture_data
-                    .texture_atlas
-                    .sub_textures
-                    .iter()
-                    .map(|sub_texture| sub_texture.name.to_string())
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_else(Vec::new),
+    let mut register =
+        |name: &'static str, texture: &Handle<Image>, data: &Handle<TextureAtlasData>| {
+            RegisteredAtlas::new(
+                name,
+                texture,
+                texture_atlas_data(data),
+                &images,
+                &mut texture_atlases,
+            )
+        };
+    let [donuts, emotes, face, hair, skin, shirts, pants, shoes] = my_assets
+        .atlas_sources()
+        .map(|(name, texture, data)| register(name, texture, data));
+    let registry = AtlasRegistry {
+        donuts,
+        emotes,
+        face,
+        hair,
+        skin,
+        shirts,
+        pants,
+        shoes,
+    };
+
+    match alignment_tables.get(&my_assets.alignment_table) {
+        Some(alignment_table) => errors.extend(alignment_table.validate(&registry)),
+        None => errors.push("The alignment table failed to load".to_string()),
+    }
+
+    commands.insert_resource(registry);
+    commands.insert_resource(ingredients);
+    commands.insert_resource(faces_metadata);
+    commands.insert_resource(hair_matadata);
+    commands.insert_resource(body_metadata);
+
+    if !errors.is_empty() {
+        for error in errors.iter() {
+            error!("{}", error);
+        }
+        commands.insert_resource(AssetErrors(errors));
+    }
+}
+
+// Problems with the assets, shown on the `AssetError` screen instead of the main menu
+pub struct AssetErrors(pub Vec<String>);
+
+// Handles of everything in a collection, checked until it's loaded
+pub struct LoadingAssets(Vec<HandleUntyped>);
+
+// Goes to the `AssetError` screen as soon as an asset of the collection fails
+// to load, when the loading state would keep waiting for it forever
+pub fn check_loading<A: AssetCollection>(world: &mut World) {
+    if !world.contains_resource::<LoadingAssets>() {
+        let handles = A::load(world);
+        world.insert_resource(LoadingAssets(handles));
+    }
+
+    let asset_server = world.resource::<AssetServer>();
+    let handles = &world.resource::<LoadingAssets>().0;
+    let load_state = asset_server.get_group_load_state(handles.iter().map(|handle| handle.id));
+    if load_state == LoadState::Loaded {
+        world.remove_resource::<LoadingAssets>();
+        return;
+    }
+
+    let errors: Vec<_> = handles
+        .iter()
+        .filter(|handle| asset_server.get_load_state(handle.id) == LoadState::Failed)
+        .map(|handle| match asset_server.get_handle_path(handle.id) {
+            Some(path) => format!("{} failed to load", path.path().display()),
+            None => "An asset failed to load".to_string(),
+        })
+        .collect();
+    if errors.is_empty() {
+        return;
+    }
+
+    for error in errors.iter() {
+        error!("{}", error);
+    }
+    // With whatever did load, fonts included, for the error screen
+    if !world.contains_resource::<A>() {
+        let collection = A::create(world);
+        world.insert_resource(collection);
+    }
+    world.remove_resource::<LoadingAssets>();
+    world.insert_resource(AssetErrors(errors));
+    world
+        .resource_mut::<State<AppState>>()
+        .set(AppState::AssetError)
+        .ok();
+}
+
+fn character_metadata(
+    my_assets: &MyAssets,
+    texture_atlas_data_assets: &Assets<TextureAtlasData>,
+) -> (FacesMetadata, HairMetadata, BodyMetadata) {
+    let texture_atlas_data =
+        |handle: &Handle<TextureAtlasData>| texture_atlas_data_assets.get(handle);
+
+    let faces_metadata = texture_atlas_data(&my_assets.face_texture_data)
+        .map(FacesMetadata::new)
+        .unwrap_or_default();
+
+    let hair_matadata = texture_atlas_data(&my_assets.hair_texture_data)
+        .map(HairMetadata::new)
+        .unwrap_or_default();
+
+    let body_metadata = match (
+        texture_atlas_data(&my_assets.skin_texture_data),
+        texture_atlas_data(&my_assets.shirts_texture_data),
+        texture_atlas_data(&my_assets.pants_texture_data),
+        texture_atlas_data(&my_assets.shoes_texture_data),
+    ) {
+        (Some(skin), Some(shirts), Some(pants), Some(shoes)) => {
+            BodyMetadata::new(skin, shirts, pants, shoes)
+        }
+        _ => BodyMetadata::default(),
     };
 
-    let mut skin_atlas =
-        TextureAtlas::new_empty(my_assets.skin_texture.clone(), Vec2::new(1024., 1024.));
-    skin_atlas.fill_textures(texture_atlas_data_assets.get(&my_assets.skin_texture_data));
+    (faces_metadata, hair_matadata, body_metadata)
+}
+
+fn modified<T: Asset>(event: &AssetEvent<T>) -> Option<Handle<T>> {
+    match event {
+        AssetEvent::Modified { handle } => Some(handle.clone_weak()),
+        _ => None,
+    }
+}
 
-    let handles = Atlases {
-        donuts_atlas: texture_atlases.add(donuts_atlas),
-        emotes_atlas: texture_atlases.add(emotes_atlas),
-        face_atlas: texture_atlases.add(face_atlas),
-        hair_atlas: texture_atlases.add(hair_atlas),
-        skin_atlas: texture_atlases.add(skin_atlas),
+// Re-applies atlases and the ingredient catalog changed on disk while the game runs.
+// All of it is checked before anything changes: the atlases still pass `validate`,
+// the catalog fits the new donuts atlas with the same layout, and every sprite in use,
+// drawn or in a customer's look, is still there, found by name wherever it moved to
+#[allow(clippy::too_many_arguments)]
+pub fn reload_assets(
+    mut commands: Commands,
+    my_assets: Option<Res<MyAssets>>,
+    registry: Option<ResMut<AtlasRegistry>>,
+    ingredients: Option<ResMut<Ingredients>>,
+    mut ev_texture_atlas_data: EventReader<AssetEvent<TextureAtlasData>>,
+    mut ev_images: EventReader<AssetEvent<Image>>,
+    mut ev_ingredient_catalogs: EventReader<AssetEvent<IngredientCatalog>>,
+    images: Res<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    texture_atlas_data_assets: Res<Assets<TextureAtlasData>>,
+    ingredient_catalogs: Res<Assets<IngredientCatalog>>,
+    mut sprites: Query<(Entity, &Handle<TextureAtlas>, &mut TextureAtlasSprite)>,
+    mut appearances: Query<(Entity, &mut Appearance)>,
+) {
+    let modified_data: Vec<_> = ev_texture_atlas_data.iter().filter_map(modified).collect();
+    let modified_images: Vec<_> = ev_images.iter().filter_map(modified).collect();
+    let modified_catalogs: Vec<_> = ev_ingredient_catalogs.iter().filter_map(modified).collect();
+
+    // Still loading
+    let (my_assets, mut registry, mut ingredients) = match (my_assets, registry, ingredients) {
+        (Some(my_assets), Some(registry), Some(ingredients)) => (my_assets, registry, ingredients),
+        _ => return,
     };
 
-    commands.insert_resource(handles);
+    let atlases_modified = my_assets.atlas_sources().iter().any(|(_, texture, data)| {
+        modified_data.contains(data) || modified_images.contains(texture)
+    });
+    if !atlases_modified && !modified_catalogs.contains(&my_assets.ingredient_catalog) {
+        return;
+    }
+
+    let texture_atlas_data =
+        |handle: &Handle<TextureAtlasData>| texture_atlas_data_assets.get(handle);
+    let mut errors = validate(
+        &my_assets
+            .atlas_sources()
+            .map(|(name, _, data)| (name, texture_atlas_data(data))),
+    );
+
+    let reloaded_ingredients = match (
+        ingredient_catalogs.get(&my_assets.ingredient_catalog),
+        texture_atlas_data(&my_assets.donuts_texture_data),
+    ) {
+        (Some(catalog), Some(donuts_texture_data)) => match catalog.resolve(donuts_texture_data) {
+            Ok(reloaded) if reloaded.same_layout(&ingredients) => Some(reloaded),
+            Ok(_) => {
+                errors.push("Adding or removing layers or ingredients needs a restart".to_string());
+                None
+            }
+            Err(catalog_errors) => {
+                errors.extend(catalog_errors);
+                None
+            }
+        },
+        (None, _) => {
+            errors.push("The ingredient catalog failed to load".to_string());
+            None
+        }
+        // Reported by `validate`
+        (_, None) => None,
+    };
+
+    let remaps: HashMap<&str, Vec<Option<usize>>> = my_assets
+        .atlas_sources()
+        .into_iter()
+        .filter_map(|(name, _, data)| {
+            Some((name, registry.get(name)?.remap(texture_atlas_data(data)?)))
+        })
+        .collect();
+    let mut missing = BTreeSet::new();
+    let mut remap = |atlas: &'static str, index: &mut usize| match remaps
+        .get(atlas)
+        .and_then(|indices| indices.get(*index).copied().flatten())
+    {
+        Some(moved) => *index = moved,
+        None => {
+            missing.insert((atlas, *index));
+        }
+    };
+
+    let moved_sprites: Vec<_> = sprites
+        .iter()
+        .filter_map(|(entity, handle, sprite)| {
+            let mut index = sprite.index;
+            remap(registry.find(handle)?.name, &mut index);
+            Some((entity, index))
+        })
+        .collect();
+    let moved_appearances: Vec<_> = appearances
+        .iter()
+        .map(|(entity, appearance)| {
+            let mut appearance = *appearance;
+            for (atlas, index) in appearance_sprites(&mut appearance) {
+                remap(atlas, index);
+            }
+            (entity, appearance)
+        })
+        .collect();
+
+    errors.extend(missing.into_iter().map(|(atlas, index)| {
+        format!(
+            "The {} atlas no longer has {}, which is in use",
+            atlas,
+            registry
+                .get(atlas)
+                .and_then(|registered_atlas| registered_atlas.sprite_name(index))
+                .unwrap_or("a sprite")
+        )
+    }));
+    if !errors.is_empty() {
+        for error in errors.iter() {
+            warn!("Not reloading the assets: {}", error);
+        }
+        return;
+    }
+
+    for (name, texture, data) in my_assets.atlas_sources() {
+        if let Some(registered_atlas) = registry.get_mut(name) {
+            registered_atlas.rebuild(
+                texture,
+                texture_atlas_data(data),
+                &images,
+                &mut texture_atlases,
+            );
+        }
+    }
+    for (entity, index) in moved_sprites {
+        if let Ok((_, _, mut sprite)) = sprites.get_mut(entity) {
+            if sprite.index != index {
+                sprite.index = index;
+            }
+        }
+    }
+    for (entity, moved) in moved_appearances {
+        if let Ok((_, mut appearance)) = appearances.get_mut(entity) {
+            if *appearance != moved {
+                *appearance = moved;
+            }
+        }
+    }
+    if let Some(reloaded) = reloaded_ingredients {
+        *ingredients = reloaded;
+    }
+    info!("Reloaded the atlases and the ingredient catalog");
+
+    let (faces_metadata, hair_matadata, body_metadata) =
+        character_metadata(&my_assets, &texture_atlas_data_assets);
     commands.insert_resource(faces_metadata);
     commands.insert_resource(hair_matadata);
+    commands.insert_resource(body_metadata);
 }
 
-// JSON converted from XML via https://javadev.github.io/xml-to-json/
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid)]
-#[uuid = "c16f5026-27f4-4a38-902e-619a2da113bc"]
-pub struct TextureAtlasData {
-    #[serde(rename(deserialize = "TextureAtlas"))]
-    texture_atlas: TextureAtlasObject,
+// Sprite indices of a customer's look, with the atlas each of them is in
+fn appearance_sprites(appearance: &mut Appearance) -> Vec<(&'static str, &mut usize)> {
+    let mut sprites = vec![
+        ("skin", &mut appearance.skin),
+        ("face", &mut appearance.face),
+        ("hair", &mut appearance.hair),
+    ];
+
+    if let Some(body) = &mut appearance.body {
+        sprites.extend([
+            ("skin", &mut body.neck),
+            ("skin", &mut body.arm),
+            ("skin", &mut body.hand),
+            ("shirts", &mut body.shirt),
+            ("pants", &mut body.hips),
+            ("pants", &mut body.leg),
+            ("shoes", &mut body.shoe),
+        ]);
+        if let Some(sleeve) = &mut body.sleeve {
+            sprites.push(("shirts", sleeve));
+        }
+    }
+
+    sprites
 }
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid)]
-#[uuid = "39193fb7-499d-4698-8b49-d1886ac0754c"]
-struct TextureAtlasObject {
-    #[serde(rename(deserialize = "SubTexture"))]
-    sub_textures: Vec<SubTexture>,
+// Finds what the game would otherwise crash on, or quietly draw wrong, once it starts
+fn validate(atlases: &[(&str, Option<&TextureAtlasData>)]) -> Vec<String> {
+    let mut errors = vec![];
+    let atlas = |wanted: &str| {
+        atlases
+            .iter()
+            .find(|(name, _)| *name == wanted)
+            .and_then(|(_, texture_atlas_data)| *texture_atlas_data)
+    };
+
+    for (name, texture_atlas_data) in atlases {
+        match texture_atlas_data {
+            None => errors.push(format!("The {} atlas failed to load", name)),
+            Some(texture_atlas_data) if texture_atlas_data.sub_textures.is_empty() => {
+                errors.push(format!("The {} atlas has no sprites", name))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(emotes_texture_data) = atlas("emotes") {
+        for emote in Emo::ALL
+            .iter()
+            .map(|emotion| emotion.sprite())
+            .chain(Hint::EMOTES)
+        {
+            if emotes_texture_data.index_of(emote).is_none() {
+                errors.push(format!("The emotes atlas has no {} sprite", emote));
+            }
+        }
+    }
+
+    // Hair styles are picked among every sprite of their atlas, but faces only among these
+    if let Some(face_texture_data) = atlas("face") {
+        if FacesMetadata::new(face_texture_data)
+            .face_indexes
+            .is_empty()
+        {
+            errors.push("The face atlas has no face sprites".to_string());
+        }
+    }
+
+    errors
+}
+
+// Reads the data game logic depends on straight from the assets folder,
+// for running without a renderer or an asset server. Exits with what's
+// broken, as there is no error screen to show it on
+pub fn init_headless(mut commands: Commands) {
+    if let Err(errors) = read_headless(&mut commands) {
+        eprintln!("Broken assets:\n{}", errors.join("\n"));
+        std::process::exit(1);
+    }
+}
+
+fn read_headless(commands: &mut Commands) -> Result<(), Vec<String>> {
+    let read = |path: &str| read_atlas(path).map_err(|err| vec![err]);
+    let donuts_texture_data = read("Donuts/Spritesheet/donuts_sheet.xml")?;
+    let ingredient_catalog: IngredientCatalog =
+        read_json("Donuts/ingredients.catalog.json").map_err(|err| vec![err])?;
+    let emotes_texture_data = read("Emote Pack/Spritesheets/vector_style1.xml")?;
+    let face_texture_data = read("Character Pack/Spritesheet/sheet_face.xml")?;
+    let hair_texture_data = read("Character Pack/Spritesheet/sheet_hair.xml")?;
+    let skin_texture_data = read("Character Pack/Spritesheet/sheet_skin.xml")?;
+    let shirts_texture_data = read("Character Pack/Spritesheet/sheet_shirts.xml")?;
+    let pants_texture_data = read("Character Pack/Spritesheet/sheet_pants.xml")?;
+    let shoes_texture_data = read("Character Pack/Spritesheet/sheet_shoes.xml")?;
+
+    let mut errors = validate(&[
+        ("donuts", Some(&donuts_texture_data)),
+        ("emotes", Some(&emotes_texture_data)),
+        ("face", Some(&face_texture_data)),
+        ("hair", Some(&hair_texture_data)),
+        ("skin", Some(&skin_texture_data)),
+        ("shirts", Some(&shirts_texture_data)),
+        ("pants", Some(&pants_texture_data)),
+        ("shoes", Some(&shoes_texture_data)),
+    ]);
+    let ingredients = ingredient_catalog
+        .resolve(&donuts_texture_data)
+        .unwrap_or_else(|catalog_errors| {
+            errors.extend(catalog_errors);
+            Ingredients::default()
+        });
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    commands.insert_resource(ingredients);
+    commands.insert_resource(FacesMetadata::new(&face_texture_data));
+    commands.insert_resource(HairMetadata::new(&hair_texture_data));
+    commands.insert_resource(BodyMetadata::new(
+        &skin_texture_data,
+        &shirts_texture_data,
+        &pants_texture_data,
+        &shoes_texture_data,
+    ));
+    Ok(())
+}
+
+// Same lookup as the default asset server: next to the manifest or the executable
+fn assets_dir() -> PathBuf {
+    std::env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(PathBuf::from))
+        })
+        .unwrap_or_default()
+        .join("assets")
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    let path = assets_dir().join(path);
+    let bytes = std::fs::read(&path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+    serde_json::from_slice(&bytes)
+        .map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
 }
 
+fn read_atlas(path: &str) -> Result<TextureAtlasData, String> {
+    let path = assets_dir().join(path);
+    let xml = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+    TextureAtlasData::from_xml(&xml)
+        .map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+}
+
+// Kenney atlas, read from its original XML file: a `TextureAtlas` element
+// with a `SubTexture` element for every sprite
 #[derive(bevy::reflect::TypeUuid)]
-#[uuid = "89ce27f6-46d4-4e27-83f6-0ffdc2ad6cf2"]
+#[uuid = "c16f5026-27f4-4a38-902e-619a2da113bc"]
+pub struct TextureAtlasData {
+    sub_textures: Vec<SubTexture>,
+    indices: HashMap<String, usize>,
+}
+
 struct SubTexture {
-    #[allow(dead_code)]
     name: String,
     x: i32,
     y: i32,
@@ -218,42 +977,97 @@ struct SubTexture {
     height: i32,
 }
 
-#[derive(serde::Deserialize)]
-struct SubTextureRaw {
-    #[allow(dead_code)]
-    #[serde(rename(deserialize = "-name"))]
-    name: String,
-    #[serde(rename(deserialize = "-x"))]
-    x: String,
-    #[serde(rename(deserialize = "-y"))]
-    y: String,
-    #[serde(rename(deserialize = "-width"))]
-    width: String,
-    #[serde(rename(deserialize = "-height"))]
-    height: String,
-}
-
-impl<'de> serde::Deserialize<'de> for SubTexture {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let raw = SubTextureRaw::deserialize(deserializer)?;
+#[derive(Debug)]
+pub struct AtlasXmlError(String);
 
-        let name = raw.name;
-        let x = raw.x.parse::<i32>().unwrap();
-        let y = raw.y.parse::<i32>().unwrap();
-        let width = raw.width.parse::<i32>().unwrap();
-        let height = raw.height.parse::<i32>().unwrap();
+impl fmt::Display for AtlasXmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid texture atlas: {}", self.0)
+    }
+}
 
-        Ok(SubTexture {
-            name,
-            x,
-            y,
-            width,
-            height,
+impl std::error::Error for AtlasXmlError {}
+
+impl TextureAtlasData {
+    pub fn from_xml(xml: &str) -> Result<Self, AtlasXmlError> {
+        let document =
+            roxmltree::Document::parse(xml).map_err(|err| AtlasXmlError(err.to_string()))?;
+        let atlas = document.root_element();
+        if !atlas.has_tag_name("TextureAtlas") {
+            return Err(AtlasXmlError("no TextureAtlas element".to_string()));
+        }
+
+        let mut sub_textures = vec![];
+        for sub_texture in atlas
+            .children()
+            .filter(|node| node.has_tag_name("SubTexture"))
+        {
+            let attribute = |key: &str| {
+                sub_texture
+                    .attribute(key)
+                    .ok_or_else(|| AtlasXmlError(format!("SubTexture without a {} attribute", key)))
+            };
+            let number = |key: &str| {
+                attribute(key)?
+                    .parse::<i32>()
+                    .map_err(|err| AtlasXmlError(format!("SubTexture with a bad {}: {}", key, err)))
+            };
+
+            sub_textures.push(SubTexture {
+                name: attribute("name")?.to_string(),
+                x: number("x")?,
+                y: number("y")?,
+                width: number("width")?,
+                height: number("height")?,
+            });
+        }
+
+        let indices = sub_textures
+            .iter()
+            .enumerate()
+            .map(|(index, sub_texture)| (sub_texture.name.clone(), index))
+            .collect();
+
+        Ok(Self {
+            sub_textures,
+            indices,
         })
     }
+
+    // Sprite names without the extension, with their index
+    fn names(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.sub_textures
+            .iter()
+            .enumerate()
+            .map(|(index, sub_texture)| {
+                let name = sub_texture.name.as_str();
+                (index, name.strip_suffix(".png").unwrap_or(name))
+            })
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+}
+
+pub struct TextureAtlasLoader;
+
+impl AssetLoader for TextureAtlasLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let texture_atlas_data = TextureAtlasData::from_xml(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(texture_atlas_data));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["xml"]
+    }
 }
 
 trait FillTextures {
@@ -268,7 +1082,7 @@ impl FillTextures for TextureAtlas {
                 width,
                 height,
                 ..
-            } in texture_data.texture_atlas.sub_textures.iter()
+            } in texture_data.sub_textures.iter()
             {
                 self.add_texture(bevy::sprite::Rect {
                     min: Vec2::new(*x as f32, *y as f32),
@@ -278,3 +1092,451 @@ impl FillTextures for TextureAtlas {
         }
     }
 }
+
+// Where the body parts go, and anchors of the sprites that don't line up with
+// the head when centered, by atlas and sprite name, for hair, faces and body parts alike
+#[derive(Default, serde::Serialize, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "8e3f2b71-6c4a-4d0b-a5e9-1f7c3d2b6a48"]
+pub struct AlignmentTable {
+    #[serde(default)]
+    body: BTreeMap<String, Placement>,
+    #[serde(default)]
+    sprites: BTreeMap<String, BTreeMap<String, [f32; 2]>>,
+}
+
+impl AlignmentTable {
+    pub const PATH: &'static str = "Character Pack/alignment.json";
+
+    pub fn placement(&self, part: BodyPart) -> Option<Placement> {
+        self.body.get(part.name()).copied()
+    }
+
+    // Atlases with sprites to align, even if none of them needs an anchor yet
+    pub fn atlases(&self) -> impl Iterator<Item = &str> {
+        self.sprites.keys().map(String::as_str)
+    }
+
+    pub fn anchor(&self, atlas: &str, sprite: &str) -> Option<Vec2> {
+        self.sprites
+            .get(atlas)
+            .and_then(|sprites| sprites.get(sprite))
+            .map(|[x, y]| Vec2::new(*x, *y))
+    }
+
+    pub fn set_anchor(&mut self, atlas: &str, sprite: &str, anchor: Vec2) {
+        self.sprites
+            .entry(atlas.to_string())
+            .or_default()
+            .insert(sprite.to_string(), anchor.to_array());
+    }
+
+    fn validate(&self, registry: &AtlasRegistry) -> Vec<String> {
+        let mut errors = vec![];
+
+        for part in BodyPart::ALL {
+            if self.placement(part).is_none() {
+                errors.push(format!(
+                    "The alignment table has no placement for the {}",
+                    part.name()
+                ));
+            }
+        }
+
+        for (atlas, sprites) in self.sprites.iter() {
+            match registry.get(atlas) {
+                Some(registered_atlas) => {
+                    for sprite in sprites.keys() {
+                        if registered_atlas.index_of(sprite).is_none() {
+                            errors.push(format!(
+                                "The alignment table names an unknown {} sprite {}",
+                                atlas, sprite
+                            ));
+                        }
+                    }
+                }
+                None => errors.push(format!(
+                    "The alignment table names an unknown atlas {}",
+                    atlas
+                )),
+            }
+        }
+
+        errors
+    }
+
+    // Back into the assets folder, to keep what was tuned in the alignment view
+    pub fn store(&self) -> std::io::Result<PathBuf> {
+        let path = assets_dir().join(Self::PATH);
+        std::fs::write(&path, serde_json::to_vec_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+// Anchors of sprites drawn from the registered atlases
+#[derive(SystemParam)]
+pub struct Alignment<'w, 's> {
+    my_assets: Res<'w, MyAssets>,
+    alignment_tables: Res<'w, Assets<AlignmentTable>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> Alignment<'w, 's> {
+    pub fn anchor(&self, atlas: &RegisteredAtlas, index: usize) -> Option<Anchor> {
+        let sprite = atlas.sprite_name(index)?;
+        self.alignment_tables
+            .get(&self.my_assets.alignment_table)?
+            .anchor(atlas.name, sprite)
+            .map(Anchor::Custom)
+    }
+
+    pub fn placement(&self, part: BodyPart) -> Option<Placement> {
+        self.alignment_tables
+            .get(&self.my_assets.alignment_table)?
+            .placement(part)
+    }
+}
+
+// Maps sub-textures of the donuts atlas to ingredients, layer by layer
+#[derive(serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "5b0d7a4e-3c1f-4f0e-9d7b-2f6a8c1e4b93"]
+pub struct IngredientCatalog {
+    layers: Vec<LayerEntry>,
+    ingredients: Vec<IngredientEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct LayerEntry {
+    id: String,
+    label: String,
+}
+
+#[derive(serde::Deserialize)]
+struct IngredientEntry {
+    sprite: String,
+    layer: String,
+    name: String,
+    // Paid for every donut made with the ingredient
+    #[serde(default)]
+    cost: i32,
+    #[serde(default)]
+    flavors: Vec<String>,
+}
+
+impl IngredientCatalog {
+    // Fails on entries naming unknown layers or sprites, and on layers left without ingredients
+    pub fn resolve(
+        &self,
+        texture_atlas_data: &TextureAtlasData,
+    ) -> Result<Ingredients, Vec<String>> {
+        let mut errors = vec![];
+        let mut ingredients = Ingredients {
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| Layer {
+                    label: layer.label.clone(),
+                    ingredients: vec![],
+                })
+                .collect(),
+        };
+
+        for entry in self.ingredients.iter() {
+            let layer = self.layers.iter().position(|layer| layer.id == entry.layer);
+            let sprite_index = texture_atlas_data.index_of(&entry.sprite);
+
+            match (layer, sprite_index) {
+                (Some(layer), Some(sprite_index)) => {
+                    ingredients.layers[layer].ingredients.push(Ingredient {
+                        name: entry.name.clone(),
+                        sprite_index,
+                        cost: entry.cost,
+                        flavors: entry.flavors.clone(),
+                    })
+                }
+                (None, _) => errors.push(format!(
+                    "Unknown layer {} in the ingredient catalog",
+                    entry.layer
+                )),
+                (_, None) => errors.push(format!(
+                    "Unknown sprite {} in the ingredient catalog",
+                    entry.sprite
+                )),
+            }
+        }
+
+        if ingredients.layers.is_empty() {
+            errors.push("The ingredient catalog has no layers".to_string());
+        }
+        if ingredients.layers.len() > LAYER_KEYS.len() {
+            errors.push(format!(
+                "The ingredient catalog has {} layers, but there are keys for only {}",
+                ingredients.layers.len(),
+                LAYER_KEYS.len()
+            ));
+        }
+        for layer in ingredients.layers.iter() {
+            if layer.ingredients.is_empty() {
+                errors.push(format!("The {} layer has no ingredients", layer.label));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(ingredients)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atlas_from_xml() {
+        let atlas = TextureAtlasData::from_xml(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<TextureAtlas imagePath="sheet.png">
+	<SubTexture name="donut_1.png" x="0" y="0" width="264" height="264"/>
+	<SubTexture name='glazing_1.png' x="226" y="1668" width="226" height="226" />
+</TextureAtlas>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            atlas.names().collect::<Vec<_>>(),
+            vec![(0, "donut_1"), (1, "glazing_1")]
+        );
+        assert_eq!(atlas.index_of("glazing_1.png"), Some(1));
+        assert_eq!(atlas.index_of("glazing_1"), None);
+        assert_eq!(
+            (
+                atlas.sub_textures[1].x,
+                atlas.sub_textures[1].y,
+                atlas.sub_textures[1].width,
+                atlas.sub_textures[1].height
+            ),
+            (226, 1668, 226, 226)
+        );
+
+        assert!(TextureAtlasData::from_xml("<SubTexture name=\"a.png\"/>").is_err());
+        assert!(TextureAtlasData::from_xml(
+            "<TextureAtlas><SubTexture name=\"a.png\" x=\"0\" y=\"0\" width=\"ten\" height=\"1\"/></TextureAtlas>"
+        )
+        .is_err());
+        assert!(TextureAtlasData::from_xml("<TextureAtlas><SubTexture").is_err());
+    }
+
+    fn atlas(sprites: &[&str]) -> TextureAtlasData {
+        let sub_textures: String = sprites
+            .iter()
+            .map(|name| {
+                format!(
+                    r#"<SubTexture name="{}.png" x="0" y="0" width="1" height="1"/>"#,
+                    name
+                )
+            })
+            .collect();
+        TextureAtlasData::from_xml(&format!("<TextureAtlas>{}</TextureAtlas>", sub_textures))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_atlas_from_xml_with_comments_and_escapes() {
+        let atlas = TextureAtlasData::from_xml(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Created with <TexturePacker> -->
+<?xml-stylesheet href="atlas.css"?>
+<TextureAtlas imagePath="sheet.png">
+	<!-- <SubTexture name="commented_out.png" x="0" y="0" width="1" height="1"/> -->
+	<SubTexture name="salt &amp; pepper.png" x="0" y="0" width="264" height="264"/>
+	<SubTexture name="&quot;fancy&quot; &lt;glaze&gt;.png" x="1" y="2" width="3" height="4"/>
+</TextureAtlas>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            atlas.names().collect::<Vec<_>>(),
+            vec![(0, "salt & pepper"), (1, "\"fancy\" <glaze>")]
+        );
+    }
+
+    #[test]
+    fn test_broken_atlas_fails_loading() {
+        let broken = "<TextureAtlas><SubTexture name=\"a.png\" x=\"0\" y=\"0\" width=\"ten\" height=\"1\"/></TextureAtlas>";
+
+        let err = TextureAtlasData::from_xml(broken)
+            .err()
+            .expect("the width is not a number");
+        assert!(err.to_string().contains("bad width"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate() {
+        let donuts = atlas(&["donut_1", "glazing_1"]);
+        let emotes = atlas(&[
+            "emote_faceAngry",
+            "emote_faceHappy",
+            "emote_faceSad",
+            "emote_heart",
+            "emote_heartBroken",
+            "emote_question",
+            "emote_drop",
+            "emote_stars",
+        ]);
+        let few_emotes = atlas(&["emote_faceAngry", "emote_faceHappy", "emote_faceSad"]);
+        let face = atlas(&["face1", "blackBrow1"]);
+        let empty = atlas(&[]);
+
+        assert!(validate(&[
+            ("donuts", Some(&donuts)),
+            ("emotes", Some(&emotes)),
+            ("face", Some(&face)),
+        ])
+        .is_empty());
+        assert_eq!(
+            validate(&[
+                ("donuts", None),
+                ("emotes", Some(&few_emotes)),
+                ("face", Some(&empty)),
+            ]),
+            vec![
+                "The donuts atlas failed to load",
+                "The face atlas has no sprites",
+                "The emotes atlas has no emote_heart.png sprite",
+                "The emotes atlas has no emote_heartBroken.png sprite",
+                "The emotes atlas has no emote_question.png sprite",
+                "The emotes atlas has no emote_drop.png sprite",
+                "The emotes atlas has no emote_stars.png sprite",
+                "The face atlas has no face sprites",
+            ]
+        );
+
+        let catalog: IngredientCatalog = serde_json::from_str(
+            r#"{
+                "layers": [{"id": "base", "label": "Base"}, {"id": "glazing", "label": "Glazing"}],
+                "ingredients": [
+                    {"sprite": "donut_1.png", "layer": "base", "name": "Plain"},
+                    {"sprite": "donut_9.png", "layer": "base", "name": "Missing"},
+                    {"sprite": "glazing_1.png", "layer": "topping", "name": "Lost"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            catalog.resolve(&donuts).err(),
+            Some(vec![
+                "Unknown sprite donut_9.png in the ingredient catalog".to_string(),
+                "Unknown layer topping in the ingredient catalog".to_string(),
+                "The Glazing layer has no ingredients".to_string(),
+            ])
+        );
+
+        // One layer more than there are keys for
+        let layers = (0..=LAYER_KEYS.len())
+            .map(|layer| format!(r#"{{"id": "{0}", "label": "{0}"}}"#, layer))
+            .collect::<Vec<_>>();
+        let ingredients = (0..=LAYER_KEYS.len())
+            .map(|layer| {
+                format!(
+                    r#"{{"sprite": "donut_1.png", "layer": "{}", "name": "Plain"}}"#,
+                    layer
+                )
+            })
+            .collect::<Vec<_>>();
+        let catalog: IngredientCatalog = serde_json::from_str(&format!(
+            r#"{{"layers": [{}], "ingredients": [{}]}}"#,
+            layers.join(", "),
+            ingredients.join(", ")
+        ))
+        .unwrap();
+        assert_eq!(
+            catalog.resolve(&donuts).err(),
+            Some(vec![
+                "The ingredient catalog has 7 layers, but there are keys for only 6".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_remap() {
+        let hair = RegisteredAtlas {
+            name: "hair",
+            handle: Handle::default(),
+            indices: HashMap::new(),
+            sprite_names: vec![
+                "a.png".to_string(),
+                "b.png".to_string(),
+                "c.png".to_string(),
+            ],
+        };
+        assert_eq!(
+            hair.remap(&atlas(&["c", "a"])),
+            vec![Some(1), None, Some(0)]
+        );
+
+        let mut appearance = Appearance {
+            skin: 1,
+            face: 2,
+            hair: 3,
+            body: Some(Body {
+                neck: 4,
+                arm: 5,
+                hand: 6,
+                shirt: 7,
+                sleeve: None,
+                hips: 8,
+                leg: 9,
+                shoe: 10,
+            }),
+        };
+        for (atlas, index) in appearance_sprites(&mut appearance) {
+            if atlas == "hair" || atlas == "pants" {
+                *index += 100;
+            }
+        }
+        assert_eq!((appearance.skin, appearance.hair), (1, 103));
+        assert_eq!(
+            appearance
+                .body
+                .map(|body| (body.neck, body.hips, body.leg, body.shoe)),
+            Some((4, 108, 109, 10))
+        );
+    }
+
+    #[test]
+    fn test_alignment_table() {
+        let mut table: AlignmentTable = serde_json::from_str(
+            r#"{
+                "body": {"neck": {"joint": [0.0, -70.0], "anchor": [0.0, 0.5], "z": -0.6}},
+                "sprites": {"hair": {"blackMan1.png": [0.0, -0.4]}}
+            }"#,
+        )
+        .unwrap();
+
+        let neck = table.placement(BodyPart::Neck).unwrap();
+        assert_eq!(neck.joint(), Vec2::new(0., -70.));
+        assert!(!neck.both_sides);
+        assert_eq!(table.placement(BodyPart::Leg), None);
+
+        assert_eq!(
+            table.anchor("hair", "blackMan1.png"),
+            Some(Vec2::new(0., -0.4))
+        );
+        assert_eq!(table.anchor("hair", "blackWoman1.png"), None);
+        assert_eq!(table.anchor("face", "blackMan1.png"), None);
+
+        table.set_anchor("face", "face1.png", Vec2::new(0.1, 0.));
+        assert_eq!(table.anchor("face", "face1.png"), Some(Vec2::new(0.1, 0.)));
+
+        let shipped: AlignmentTable = read_json(AlignmentTable::PATH).unwrap();
+        assert!(BodyPart::ALL
+            .iter()
+            .all(|part| shipped.placement(*part).is_some()));
+        assert_eq!(
+            shipped.anchor("hair", "brown2Man8.png"),
+            Some(Vec2::new(0., -0.25))
+        );
+    }
+}
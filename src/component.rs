@@ -1,112 +1,235 @@
 use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use bevy::prelude::*;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-pub trait ToSpriteIndex {
-    const START_SPRITE_INDEX: usize = 0;
-    const SPRITES_COUNT: usize = 1;
+use crate::difficulty::DifficultyLevel;
+use crate::taste::Hint;
 
-    fn to_sprite_index(&self) -> usize {
-        Self::START_SPRITE_INDEX
-    }
+// Source of every random choice in the game, so a seed reproduces a run
+pub struct GameRng {
+    pub seed: u64,
+    rng: StdRng,
+}
 
-    fn cycle_right(&mut self) -> &mut Self;
-    fn cycle_left(&mut self) -> &mut Self;
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
 }
 
-#[derive(Component, PartialEq, Eq, Debug, Copy, Clone, Default)]
-pub struct Base(pub usize);
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
 
-impl ToSpriteIndex for Base {
-    const START_SPRITE_INDEX: usize = 0;
-    const SPRITES_COUNT: usize = 3;
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
 
-    fn to_sprite_index(&self) -> usize {
-        Self::START_SPRITE_INDEX + (self.0 as usize)
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
     }
 
-    fn cycle_right(&mut self) -> &mut Self {
-        self.0 = (self.0 + Self::SPRITES_COUNT + 1) % Self::SPRITES_COUNT;
-        self
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
     }
 
-    fn cycle_left(&mut self) -> &mut Self {
-        self.0 = (self.0 + Self::SPRITES_COUNT - 1) % Self::SPRITES_COUNT;
-        self
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
     }
 }
 
-#[test]
-fn test_base_cycling() {
-    let mut base = Base(0);
+pub struct Ingredient {
+    pub name: String,
+    pub sprite_index: usize,
+    pub cost: i32,
+    pub flavors: Vec<String>,
+}
+
+// One layer of a donut, drawn on top of the previous ones
+pub struct Layer {
+    pub label: String,
+    pub ingredients: Vec<Ingredient>,
+}
 
-    assert_eq!(base.cycle_left(), &Base(2));
-    assert_eq!(base.cycle_left(), &Base(1));
-    assert_eq!(base.cycle_right(), &Base(2));
-    assert_eq!(base.cycle_right(), &Base(0));
+// Ingredients available in the shop, resolved from the catalog asset
+#[derive(Default)]
+pub struct Ingredients {
+    pub layers: Vec<Layer>,
 }
 
-#[derive(Component, Copy, Clone, Default)]
-pub struct Glazing(pub usize);
+impl Ingredients {
+    pub fn choices(&self, layer: usize) -> usize {
+        self.layers
+            .get(layer)
+            .map(|layer| layer.ingredients.len())
+            .unwrap_or(0)
+    }
 
-impl ToSpriteIndex for Glazing {
-    const START_SPRITE_INDEX: usize = 3;
-    const SPRITES_COUNT: usize = 6;
+    pub fn get(&self, layer: usize, choice: usize) -> Option<&Ingredient> {
+        self.layers
+            .get(layer)
+            .and_then(|layer| layer.ingredients.get(choice))
+    }
 
-    fn to_sprite_index(&self) -> usize {
-        Self::START_SPRITE_INDEX + (self.0)
+    pub fn sprite_index(&self, layer: usize, choice: usize) -> usize {
+        self.get(layer, choice)
+            .map(|ingredient| ingredient.sprite_index)
+            .unwrap_or(0)
     }
 
-    fn cycle_right(&mut self) -> &mut Self {
-        self.0 = (self.0 + Self::SPRITES_COUNT + 1) % Self::SPRITES_COUNT;
-        self
+    pub fn name(&self, layer: usize, choice: usize) -> &str {
+        self.get(layer, choice)
+            .map(|ingredient| ingredient.name.as_str())
+            .unwrap_or("???")
     }
 
-    fn cycle_left(&mut self) -> &mut Self {
-        self.0 = (self.0 + Self::SPRITES_COUNT - 1) % Self::SPRITES_COUNT;
-        self
+    // Price of the ingredients of a donut
+    pub fn cost(&self, donut: &DonutLayers) -> i32 {
+        donut
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(layer, choice)| self.get(layer, *choice))
+            .map(|ingredient| ingredient.cost)
+            .sum()
+    }
+
+    pub fn describe(&self, donut: &DonutLayers) -> String {
+        let names = donut
+            .0
+            .iter()
+            .enumerate()
+            .map(|(layer, choice)| self.name(layer, *choice))
+            .collect::<Vec<_>>();
+
+        match names.split_first() {
+            Some((base, [])) => format!("{} donut", base),
+            Some((base, toppings)) => format!("{} donut with {}", base, toppings.join(", ")),
+            None => "donut".to_string(),
+        }
+    }
+
+    // Same number of layers and ingredients in each, so donuts and tastes still fit
+    pub fn same_layout(&self, other: &Ingredients) -> bool {
+        self.layers.len() == other.layers.len()
+            && (0..self.layers.len()).all(|layer| self.choices(layer) == other.choices(layer))
     }
 }
 
-#[derive(Component, Copy, Clone, Default)]
-pub struct Sprinkles(pub usize);
+#[cfg(test)]
+pub(crate) fn test_ingredients(choices: &[usize]) -> Ingredients {
+    Ingredients {
+        layers: choices
+            .iter()
+            .enumerate()
+            .map(|(layer, count)| Layer {
+                label: format!("layer {}", layer),
+                ingredients: (0..*count)
+                    .map(|index| Ingredient {
+                        name: format!("ingredient {}", index),
+                        sprite_index: index,
+                        cost: 1,
+                        flavors: vec![],
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
 
-impl ToSpriteIndex for Sprinkles {
-    const START_SPRITE_INDEX: usize = 13;
-    const SPRITES_COUNT: usize = 7;
+// Chosen ingredient index for every layer of a donut
+#[derive(Component, PartialEq, Eq, Hash, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DonutLayers(pub Vec<usize>);
 
-    fn to_sprite_index(&self) -> usize {
-        Self::START_SPRITE_INDEX + (self.0 as usize)
+impl DonutLayers {
+    pub fn new(ingredients: &Ingredients) -> Self {
+        DonutLayers(vec![0; ingredients.layers.len()])
     }
 
-    fn cycle_right(&mut self) -> &mut Self {
-        self.0 = (self.0 + Self::SPRITES_COUNT + 1) % Self::SPRITES_COUNT;
+    pub fn cycle_right(&mut self, layer: usize, ingredients: &Ingredients) -> &mut Self {
+        let count = ingredients.choices(layer).max(1);
+        if let Some(choice) = self.0.get_mut(layer) {
+            *choice = (*choice + count + 1) % count;
+        }
         self
     }
 
-    fn cycle_left(&mut self) -> &mut Self {
-        self.0 = (self.0 + Self::SPRITES_COUNT - 1) % Self::SPRITES_COUNT;
+    pub fn cycle_left(&mut self, layer: usize, ingredients: &Ingredients) -> &mut Self {
+        let count = ingredients.choices(layer).max(1);
+        if let Some(choice) = self.0.get_mut(layer) {
+            *choice = (*choice + count - 1) % count;
+        }
         self
     }
 }
 
+#[test]
+fn test_same_layout() {
+    let ingredients = test_ingredients(&[3, 6, 7]);
+    let mut renamed = test_ingredients(&[3, 6, 7]);
+    renamed.layers[1].ingredients[0].name = "Vanilla".to_string();
+
+    assert!(ingredients.same_layout(&renamed));
+    assert!(!ingredients.same_layout(&test_ingredients(&[3, 6, 8])));
+    assert!(!ingredients.same_layout(&test_ingredients(&[3, 6])));
+}
+
+#[test]
+fn test_layer_cycling() {
+    let ingredients = test_ingredients(&[3, 6, 7]);
+    let mut donut = DonutLayers::new(&ingredients);
+
+    assert_eq!(
+        donut.cycle_left(0, &ingredients),
+        &DonutLayers(vec![2, 0, 0])
+    );
+    assert_eq!(
+        donut.cycle_left(0, &ingredients),
+        &DonutLayers(vec![1, 0, 0])
+    );
+    assert_eq!(
+        donut.cycle_right(0, &ingredients),
+        &DonutLayers(vec![2, 0, 0])
+    );
+    assert_eq!(
+        donut.cycle_right(0, &ingredients),
+        &DonutLayers(vec![0, 0, 0])
+    );
+    assert_eq!(
+        donut.cycle_left(2, &ingredients),
+        &DonutLayers(vec![0, 0, 6])
+    );
+}
+
+// Sprite of a single donut layer
+#[derive(Component)]
+pub struct DonutLayerSprite(pub usize);
+
 #[derive(Component, Default)]
 pub struct Donut;
 
 #[derive(Bundle, Default)]
 pub struct DonutBundle {
     pub donut: Donut,
-    pub base: Base,
-    pub glazing: Glazing,
-    pub sprinkles: Sprinkles,
+    pub layers: DonutLayers,
 
     #[bundle]
     pub spatial: SpatialBundle,
 }
 
 impl DonutBundle {
-    pub fn new() -> Self {
+    pub fn new(layers: DonutLayers) -> Self {
         DonutBundle {
+            layers,
             spatial: SpatialBundle::from_transform(
                 Transform::from_translation(Vec3::new(0., -150., 1.)).with_scale(Vec3::ONE * 0.5),
             ),
@@ -118,121 +241,94 @@ impl DonutBundle {
 #[derive(Component)]
 pub struct CookingDonut;
 
-#[derive(Component, Default)]
-pub struct Taste {
-    bases: [usize; Base::SPRITES_COUNT],
-    glazing: [usize; Glazing::SPRITES_COUNT],
-    sprinkles: [usize; Sprinkles::SPRITES_COUNT],
-}
-
-impl Taste {
-    pub fn rank(&self, base: &Base, glazing: &Glazing, sprinkles: &Sprinkles) -> usize {
-        let stars = [
-            self.bases[base.0],
-            self.glazing[glazing.0],
-            self.sprinkles[sprinkles.0],
-        ];
+#[derive(Component)]
+pub struct CurrentCustomer;
 
-        // Returns 0.0..1.0
-        let weight = |r| ((r as f32 - 4.) * 2. + 7.) / 9.;
+// Stays the same when the game is saved and resumed, unlike the entity
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct CustomerId(pub u32);
 
-        let average_weight: f32 = stars.into_iter().map(weight).sum::<f32>() / stars.len() as f32;
+pub struct NextCustomerId(pub u32);
 
-        (average_weight * 5.).round() as usize
-    }
+// Bad donuts a customer still puts up with before leaving for good
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Patience(pub u32);
 
-    #[allow(dead_code)]
-    pub fn all() -> Self {
-        Self {
-            bases: [5; Base::SPRITES_COUNT],
-            glazing: [5; Glazing::SPRITES_COUNT],
-            sprinkles: [5; Sprinkles::SPRITES_COUNT],
-        }
-    }
+// Customers who left for good during the session
+pub struct LostCustomers(pub usize);
 
-    #[allow(dead_code)]
-    pub fn random() -> Self {
-        use rand::distributions::WeightedIndex;
-        use rand::prelude::*;
+// Hints customers may still give today
+pub struct HintsLeft(pub u32);
 
-        let choices = [1, 2, 3, 4, 5];
-        let weights = [1, 2, 4, 6, 3];
-        let dist = WeightedIndex::new(&weights).unwrap();
-        let mut rng = rand::thread_rng();
+// Money of the shop, may go into debt
+pub struct Cash(pub i32);
 
-        Taste {
-            bases: [0; Base::SPRITES_COUNT].map(|_| choices[dist.sample(&mut rng)]),
-            glazing: [0; Glazing::SPRITES_COUNT].map(|_| choices[dist.sample(&mut rng)]),
-            sprinkles: [0; Sprinkles::SPRITES_COUNT].map(|_| choices[dist.sample(&mut rng)]),
-        }
-    }
-}
-
-#[test]
-fn test_donut_ranking() {
-    let donut = DonutBundle::default();
-
-    assert_eq!(
-        Taste::default().rank(&donut.base, &donut.glazing, &donut.sprinkles),
-        0
-    );
-    assert_eq!(
-        Taste::all().rank(&donut.base, &donut.glazing, &donut.sprinkles),
-        5
-    );
-
-    let mut taste = Taste::default();
-    taste.bases[0] = 5;
-    taste.glazing[0] = 5;
-    taste.sprinkles[0] = 5;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 5);
+#[derive(Component)]
+pub struct CashText;
 
-    taste.bases[0] = 4;
-    taste.glazing[0] = 5;
-    taste.sprinkles[0] = 5;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 5);
+#[derive(Component)]
+pub struct ClockText;
 
-    taste.bases[0] = 4;
-    taste.glazing[0] = 4;
-    taste.sprinkles[0] = 5;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 4);
+// Loyalty and mood of the current customer
+#[derive(Component)]
+pub struct MoodText;
+
+// Sprite indices a customer is drawn with
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Appearance {
+    // Head, in the skin atlas
+    pub skin: usize,
+    pub face: usize,
+    pub hair: usize,
+    // Missing from older saves, whose customers are only heads
+    #[serde(default)]
+    pub body: Option<Body>,
+}
 
-    taste.bases[0] = 2;
-    taste.glazing[0] = 5;
-    taste.sprinkles[0] = 5;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 4);
+// Sprite indices of everything below the head, in the atlas of every part
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Body {
+    pub neck: usize,
+    pub arm: usize,
+    pub hand: usize,
+    pub shirt: usize,
+    // Bare arms without one
+    pub sleeve: Option<usize>,
+    pub hips: usize,
+    pub leg: usize,
+    pub shoe: usize,
+}
 
-    taste.bases[0] = 3;
-    taste.glazing[0] = 4;
-    taste.sprinkles[0] = 3;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 3);
+#[derive(Component, Default)]
+pub struct TransactionLog;
 
-    taste.bases[0] = 2;
-    taste.glazing[0] = 4;
-    taste.sprinkles[0] = 4;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 3);
+// Transaction log UI entry
+#[derive(Component)]
+pub struct LogEntry {
+    pub transaction: usize,
+    pub customer: CustomerId,
+}
 
-    taste.bases[0] = 1;
-    taste.glazing[0] = 4;
-    taste.sprinkles[0] = 5;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 3);
+// Which entries of the transaction log are shown
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum NotesMode {
+    #[default]
+    Everyone,
+    CurrentCustomer,
+}
 
-    taste.bases[0] = 1;
-    taste.glazing[0] = 2;
-    taste.sprinkles[0] = 3;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 2);
+#[derive(Component)]
+pub struct NotesButton;
 
-    taste.bases[0] = 1;
-    taste.glazing[0] = 2;
-    taste.sprinkles[0] = 2;
-    assert_eq!(taste.rank(&donut.base, &donut.glazing, &donut.sprinkles), 1);
-}
+#[derive(Component)]
+pub struct NotesTitle;
 
+// Picture of the current customer above their notes
 #[derive(Component)]
-pub struct CurrentCustomer;
+pub struct Portrait;
 
-#[derive(Component, Default)]
-pub struct TransactionLog;
+#[derive(Component)]
+pub struct PortraitCamera;
 
 #[derive(Bundle)]
 pub struct SalesLogBundle {
@@ -253,13 +349,34 @@ impl Default for SalesLogBundle {
     }
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Emo {
-    Angry = 15,
-    Happy = 16,
-    Sad = 17,
-    Love = 18,
-    Heartbroken = 19,
+    Angry,
+    Happy,
+    Sad,
+    Love,
+    Heartbroken,
+}
+
+impl Emo {
+    pub const ALL: [Emo; 5] = [
+        Emo::Angry,
+        Emo::Happy,
+        Emo::Sad,
+        Emo::Love,
+        Emo::Heartbroken,
+    ];
+
+    // Sprite of the emotes atlas
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            Emo::Angry => "emote_faceAngry.png",
+            Emo::Happy => "emote_faceHappy.png",
+            Emo::Sad => "emote_faceSad.png",
+            Emo::Love => "emote_heart.png",
+            Emo::Heartbroken => "emote_heartBroken.png",
+        }
+    }
 }
 
 #[derive(Component)]
@@ -274,31 +391,152 @@ pub struct Photo(pub Handle<Image>);
 
 #[derive(Component)]
 pub struct PhotoCamera;
-pub struct PhotosTakenEvent;
+pub struct PhotosTakenEvent {
+    pub transaction: usize,
+}
+
+// Asks to offer the cooking donut to the current customer
+pub struct OfferDonutEvent;
+
+// Customer reaction to an offered donut
+pub struct DonutOfferedEvent {
+    pub customer: Entity,
+    pub donut: Entity,
+    pub layers: DonutLayers,
+    pub rank: usize,
+    pub emotion: Emo,
+    pub hint: Option<Hint>,
+    // Index in the transaction history
+    pub transaction: usize,
+}
 
+// Customer loyal enough to the shop, see `Loyalty`
 #[derive(Component)]
 pub struct Regular;
 
+// How attached a customer is to the shop, moving with every donut they get
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Loyalty(pub u32);
+
+impl Loyalty {
+    pub const MAX: u32 = 6;
+    pub const REGULAR: u32 = 3;
+
+    pub fn change(&mut self, emotion: Emo) {
+        self.0 = match emotion {
+            // A single loved donut is enough to become a regular, with room
+            // to spare, so one bad donut after it isn't enough to lose them
+            Emo::Love => Self::MAX,
+            Emo::Happy => (self.0 + 1).min(Self::MAX),
+            Emo::Sad => self.0.saturating_sub(1),
+            Emo::Angry => self.0.saturating_sub(2),
+            Emo::Heartbroken => self.0.saturating_sub(3),
+        };
+    }
+
+    pub fn is_regular(&self) -> bool {
+        self.0 >= Self::REGULAR
+    }
+}
+
+#[test]
+fn test_loyalty() {
+    let mut loyalty = Loyalty::default();
+    loyalty.change(Emo::Happy);
+    assert!(!loyalty.is_regular());
+    loyalty.change(Emo::Love);
+    assert_eq!(loyalty, Loyalty(Loyalty::MAX));
+
+    loyalty.change(Emo::Sad);
+    assert!(loyalty.is_regular());
+    loyalty.change(Emo::Angry);
+    assert!(loyalty.is_regular());
+    loyalty.change(Emo::Sad);
+    assert!(!loyalty.is_regular());
+    loyalty.change(Emo::Heartbroken);
+    assert_eq!(loyalty, Loyalty(0));
+}
+
+// Last donut a customer loved
+#[derive(Component, Clone)]
+pub struct Favorite(pub DonutLayers);
+
+// What a regular feels like when they come to the booth
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Mood {
+    // Orders their favorite donut again
+    TheUsual,
+    // Doesn't want anything they had before
+    SomethingNew,
+}
+
 #[derive(Component)]
 pub struct PlayAgainButton;
 
 #[derive(Component)]
-pub struct BaseLeftButton;
+pub struct ExportButton;
+
+#[derive(Component)]
+pub struct NewGameButton;
+
+#[derive(Component)]
+pub struct ContinueButton;
+
+#[derive(Component)]
+pub struct DifficultyButton(pub DifficultyLevel);
+
+#[derive(Component)]
+pub struct QuitButton;
+
+#[derive(Component)]
+pub struct SettingsButton;
+
+#[derive(Component)]
+pub struct SettingsBackButton;
+
+#[derive(Component)]
+pub struct FullscreenButton;
+
+// Buttons of a menu, hidden while its settings page is shown
+#[derive(Component)]
+pub struct MenuButtons;
 
 #[derive(Component)]
-pub struct BaseRightButton;
+pub struct SettingsPanel;
 
 #[derive(Component)]
-pub struct GlazingLeftButton;
+pub struct PauseMenu;
 
 #[derive(Component)]
-pub struct GlazingRightButton;
+pub struct DaySummary;
 
 #[derive(Component)]
-pub struct SprinklesLeftButton;
+pub struct NextDayButton;
 
+// Sprite shown in the alignment view, on its head
 #[derive(Component)]
-pub struct SprinklesRightButton;
+pub struct AlignmentGrid;
+
+#[derive(Component)]
+pub struct AlignmentText;
+
+#[derive(Component)]
+pub struct ResumeButton;
+
+#[derive(Component)]
+pub struct MainMenuButton;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Cycle {
+    Left,
+    Right,
+}
+
+#[derive(Component)]
+pub struct LayerButton {
+    pub layer: usize,
+    pub cycle: Cycle,
+}
 
 #[derive(Component)]
 pub struct OfferButton;
@@ -308,10 +546,129 @@ pub struct NewDonutButton;
 #[derive(Component)]
 pub struct Layout;
 
+// One game, from the first customer to the game over. Its resources are
+// inserted by `setup_session` and all removed by `end_session`
+pub struct Session {
+    // Seconds since the Unix epoch
+    pub started_at: u64,
+}
+
 pub struct Line(pub VecDeque<Entity>);
 
+// Shift being played, ending when its clock runs out
+pub struct Day {
+    // Counted from 1
+    pub number: u32,
+    pub clock: Timer,
+}
+
+impl Day {
+    pub fn new(number: u32, length: f32) -> Self {
+        Self {
+            number,
+            clock: Timer::from_seconds(length, false),
+        }
+    }
+
+    pub fn seconds_left(&self) -> f32 {
+        (self.clock.duration() - self.clock.elapsed()).as_secs_f32()
+    }
+}
+
 pub struct LastUsedDonut {
-    pub base: Base,
-    pub glazing: Glazing,
-    pub sprinkles: Sprinkles,
+    pub layers: DonutLayers,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub customer: CustomerId,
+    pub layers: DonutLayers,
+    pub rank: usize,
+    pub emotion: Emo,
+    // Seconds since the Unix epoch
+    #[serde(default)]
+    pub timestamp: u64,
+    // Missing from older saves
+    #[serde(default)]
+    pub day: u32,
+    #[serde(default)]
+    pub hint: Option<Hint>,
+    // Paid by the customer
+    #[serde(default)]
+    pub revenue: i32,
+    // Paid for the ingredients
+    #[serde(default)]
+    pub cost: i32,
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// Every donut offered during the session
+#[derive(Default)]
+pub struct TransactionHistory(pub Vec<Transaction>);
+
+// Totals of a run of transactions
+#[derive(Default, PartialEq, Debug)]
+pub struct Summary {
+    pub offered: usize,
+    pub loved: usize,
+    pub revenue: i32,
+    pub costs: i32,
+}
+
+impl Summary {
+    pub fn of<'a>(transactions: impl IntoIterator<Item = &'a Transaction>) -> Self {
+        transactions
+            .into_iter()
+            .fold(Summary::default(), |mut summary, transaction| {
+                summary.offered += 1;
+                if transaction.emotion == Emo::Love {
+                    summary.loved += 1;
+                }
+                summary.revenue += transaction.revenue;
+                summary.costs += transaction.cost;
+                summary
+            })
+    }
+
+    pub fn profit(&self) -> i32 {
+        self.revenue - self.costs
+    }
+}
+
+#[test]
+fn test_summary() {
+    let transaction = |emotion, revenue, cost| Transaction {
+        customer: CustomerId(0),
+        layers: DonutLayers(vec![0, 0, 0]),
+        rank: 0,
+        emotion,
+        timestamp: 0,
+        day: 1,
+        hint: None,
+        revenue,
+        cost,
+    };
+    let history = [
+        transaction(Emo::Love, 10, 3),
+        transaction(Emo::Sad, 4, 5),
+        transaction(Emo::Happy, 6, 3),
+    ];
+
+    let summary = Summary::of(&history);
+    assert_eq!(
+        summary,
+        Summary {
+            offered: 3,
+            loved: 1,
+            revenue: 20,
+            costs: 11,
+        }
+    );
+    assert_eq!(summary.profit(), 9);
 }
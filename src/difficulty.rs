@@ -0,0 +1,216 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::save;
+use crate::taste::{TasteKind, STAR_WEIGHTS};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DifficultyLevel {
+    Easy,
+    Normal,
+    Hard,
+    // Read from `difficulty.json` in the user data directory
+    Custom,
+}
+
+impl DifficultyLevel {
+    pub const ALL: [DifficultyLevel; 4] = [
+        DifficultyLevel::Easy,
+        DifficultyLevel::Normal,
+        DifficultyLevel::Hard,
+        DifficultyLevel::Custom,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DifficultyLevel::Easy => "Easy",
+            DifficultyLevel::Normal => "Normal",
+            DifficultyLevel::Hard => "Hard",
+            DifficultyLevel::Custom => "Custom",
+        }
+    }
+}
+
+// Numbers the game rules are tuned with
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Difficulty {
+    pub level: DifficultyLevel,
+    // Customers waiting in line, not counting the regulars
+    pub line_length: usize,
+    pub regulars_to_win: usize,
+    // Starting patience of every customer
+    pub patience: u32,
+    // Customers leaving for good before the game is lost
+    pub customers_to_lose: usize,
+    // Seconds in a shift
+    pub day_length: f32,
+    // Shifts to win in, the game is lost when the last one ends
+    pub days: u32,
+    // Chance of a regular wanting something new rather than their favorite
+    pub something_new_chance: f64,
+    // Hints customers give with their reactions, every day
+    pub hints_per_day: u32,
+    pub starting_cash: i32,
+    // Also needed to win, besides the regulars
+    pub cash_to_win: i32,
+    // Paid for every star a donut gets
+    pub price_per_star: i32,
+    // How likely an ingredient is rated 1 to 5 stars
+    pub star_weights: [usize; 5],
+    // How often each kind of taste comes up
+    pub taste_kinds: Vec<(TasteKind, usize)>,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self::preset(DifficultyLevel::Normal)
+    }
+}
+
+impl Difficulty {
+    pub fn preset(level: DifficultyLevel) -> Self {
+        match level {
+            DifficultyLevel::Easy => Self {
+                level,
+                line_length: 3,
+                regulars_to_win: 2,
+                patience: 8,
+                customers_to_lose: 5,
+                day_length: 240.,
+                days: 6,
+                something_new_chance: 0.1,
+                hints_per_day: 8,
+                starting_cash: 20,
+                cash_to_win: 30,
+                price_per_star: 3,
+                star_weights: [1, 1, 3, 6, 6],
+                taste_kinds: vec![(TasteKind::Additive, 1)],
+            },
+            DifficultyLevel::Normal => Self {
+                level,
+                line_length: 4,
+                regulars_to_win: 3,
+                patience: 6,
+                customers_to_lose: 3,
+                day_length: 180.,
+                days: 5,
+                something_new_chance: 0.25,
+                hints_per_day: 4,
+                starting_cash: 10,
+                cash_to_win: 30,
+                price_per_star: 2,
+                star_weights: STAR_WEIGHTS,
+                taste_kinds: vec![
+                    (TasteKind::Additive, 4),
+                    (TasteKind::Pairwise, 2),
+                    (TasteKind::Veto, 2),
+                    (TasteKind::AtLeastOne, 2),
+                ],
+            },
+            DifficultyLevel::Hard => Self {
+                level,
+                line_length: 5,
+                regulars_to_win: 5,
+                patience: 4,
+                customers_to_lose: 2,
+                day_length: 150.,
+                days: 4,
+                something_new_chance: 0.4,
+                hints_per_day: 1,
+                starting_cash: 5,
+                cash_to_win: 60,
+                price_per_star: 2,
+                star_weights: [2, 3, 4, 4, 2],
+                taste_kinds: vec![
+                    (TasteKind::Additive, 1),
+                    (TasteKind::Pairwise, 3),
+                    (TasteKind::Veto, 3),
+                    (TasteKind::AtLeastOne, 3),
+                ],
+            },
+            DifficultyLevel::Custom => Self::custom(),
+        }
+    }
+
+    // Missing values are taken from the normal difficulty
+    fn custom() -> Self {
+        let custom = fs::read(custom_path())
+            .ok()
+            .and_then(|bytes| match serde_json::from_slice(&bytes) {
+                Ok(custom) => Some(custom),
+                Err(err) => {
+                    warn!("Ignoring broken {}: {}", custom_path().display(), err);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self {
+            level: DifficultyLevel::Custom,
+            ..custom
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DifficultyLevel::ALL
+            .into_iter()
+            .find(|level| level.label().eq_ignore_ascii_case(name))
+            .map(Self::preset)
+    }
+}
+
+pub fn custom_path() -> PathBuf {
+    save::data_dir().join("difficulty.json")
+}
+
+// Seconds between two looks at `difficulty.json`
+const RELOAD_INTERVAL: f32 = 1.;
+
+// Re-applies `difficulty.json` edited while playing the custom difficulty, which new
+// customers and days pick up. Broken edits are ignored rather than reset to normal
+pub fn reload_custom(
+    mut difficulty: ResMut<Difficulty>,
+    time: Res<Time>,
+    mut since_check: Local<f32>,
+    mut last_modified: Local<Option<Option<SystemTime>>>,
+) {
+    if difficulty.level != DifficultyLevel::Custom {
+        return;
+    }
+
+    *since_check += time.delta_seconds();
+    if *since_check < RELOAD_INTERVAL {
+        return;
+    }
+    *since_check = 0.;
+
+    let modified = fs::metadata(custom_path())
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    match last_modified.replace(modified) {
+        Some(last_modified) if last_modified != modified => {}
+        // Only noting when it was last changed
+        _ => return,
+    }
+
+    let custom = fs::read(custom_path())
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| {
+            serde_json::from_slice::<Difficulty>(&bytes).map_err(|err| err.to_string())
+        });
+    match custom {
+        Ok(custom) => {
+            *difficulty = Difficulty {
+                level: DifficultyLevel::Custom,
+                ..custom
+            };
+            info!("Reloaded {}", custom_path().display());
+        }
+        Err(err) => warn!("Ignoring broken {}: {}", custom_path().display(), err),
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::component::*;
+use crate::save;
+
+// Offer as written to the export files, with ingredient names instead of indices
+#[derive(Serialize)]
+struct ExportedTransaction<'a> {
+    timestamp: u64,
+    day: u32,
+    customer: u32,
+    layers: &'a [usize],
+    ingredients: Vec<&'a str>,
+    rank: usize,
+    emotion: Emo,
+    revenue: i32,
+    cost: i32,
+}
+
+pub fn export_dir() -> PathBuf {
+    save::data_dir().join("exports")
+}
+
+// Writes the history to a pair of CSV and JSON files named after the session,
+// returning the path of the CSV one
+pub fn export(
+    session: &Session,
+    history: &TransactionHistory,
+    ingredients: &Ingredients,
+) -> io::Result<PathBuf> {
+    let exported: Vec<_> = history
+        .0
+        .iter()
+        .map(|transaction| ExportedTransaction {
+            timestamp: transaction.timestamp,
+            day: transaction.day,
+            customer: transaction.customer.0,
+            layers: &transaction.layers.0,
+            ingredients: transaction
+                .layers
+                .0
+                .iter()
+                .enumerate()
+                .map(|(layer, choice)| ingredients.name(layer, *choice))
+                .collect(),
+            rank: transaction.rank,
+            emotion: transaction.emotion,
+            revenue: transaction.revenue,
+            cost: transaction.cost,
+        })
+        .collect();
+
+    let mut csv = String::from("timestamp,day,customer");
+    for layer in ingredients.layers.iter() {
+        csv.push(',');
+        csv.push_str(&csv_field(&layer.label));
+    }
+    csv.push_str(",rank,emotion,revenue,cost\n");
+    for transaction in exported.iter() {
+        csv.push_str(&format!(
+            "{},{},{}",
+            transaction.timestamp, transaction.day, transaction.customer
+        ));
+        for layer in 0..ingredients.layers.len() {
+            csv.push(',');
+            csv.push_str(&csv_field(
+                transaction
+                    .ingredients
+                    .get(layer)
+                    .copied()
+                    .unwrap_or_default(),
+            ));
+        }
+        csv.push_str(&format!(
+            ",{},{:?},{},{}\n",
+            transaction.rank, transaction.emotion, transaction.revenue, transaction.cost
+        ));
+    }
+
+    let stem = format!("history-{}", session.started_at);
+    let csv_path = export_dir().join(format!("{}.csv", stem));
+    let json_path = export_dir().join(format!("{}.json", stem));
+
+    fs::create_dir_all(export_dir())?;
+    fs::write(&csv_path, csv)?;
+    fs::write(json_path, serde_json::to_vec_pretty(&exported)?)?;
+
+    Ok(csv_path)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn export_history(
+    keys: Res<Input<KeyCode>>,
+    mut interactions: Query<&mut Interaction, With<ExportButton>>,
+    session: Option<Res<Session>>,
+    history: Option<Res<TransactionHistory>>,
+    ingredients: Res<Ingredients>,
+) {
+    let mut requested = keys.just_pressed(KeyCode::F2);
+
+    for mut interaction in interactions.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            requested = true;
+            *interaction = Interaction::None;
+        }
+    }
+
+    if !requested {
+        return;
+    }
+
+    if let (Some(session), Some(history)) = (session, history) {
+        match export(&session, &history, &ingredients) {
+            Ok(path) => info!("Exported the transaction history to {}", path.display()),
+            Err(err) => warn!("Failed to export the transaction history: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Dark chocolate"), "Dark chocolate");
+        assert_eq!(csv_field("Nuts, crushed"), "\"Nuts, crushed\"");
+        assert_eq!(csv_field("\"Party\""), "\"\"\"Party\"\"\"");
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use bevy::app::{AppExit, ScheduleRunnerSettings};
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::component::*;
+use crate::difficulty::Difficulty;
+use crate::{assets, AppState, DonutGamePlugin};
+
+// Game time passing every frame, regardless of the real time
+const FRAME_TIME: Duration = Duration::from_millis(100);
+
+// Plays the given number of games without a window and prints how they went
+pub fn run(seed: u64, games: usize, difficulty: Difficulty) {
+    App::new()
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::ZERO))
+        .add_plugins(MinimalPlugins)
+        .insert_resource(Input::<KeyCode>::default())
+        .insert_resource(GameRng::new(seed))
+        .insert_resource(difficulty)
+        .insert_resource(SimulatedServer::new(seed))
+        .insert_resource(Simulation {
+            games,
+            ..Default::default()
+        })
+        .add_startup_system(assets::init_headless)
+        .add_system_to_stage(CoreStage::PreUpdate, advance_clock)
+        .add_plugin(DonutGamePlugin)
+        .add_state(AppState::InGame)
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(simulated_server)
+                .with_system(record_offers),
+        )
+        .add_system_set(SystemSet::on_update(AppState::DayOver).with_system(skip_day_summary))
+        .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(finish_game))
+        .add_system_set(SystemSet::on_update(AppState::GameLost).with_system(finish_game))
+        .run();
+}
+
+// Replaces the real clock, which `MinimalPlugins` updates earlier in the frame,
+// with one advancing a fixed step, so games play the same at any speed
+fn advance_clock(mut time: ResMut<Time>, mut clock: Local<Option<(Instant, Time)>>) {
+    let (now, game_time) = clock.get_or_insert_with(|| (Instant::now(), Time::default()));
+    *now += FRAME_TIME;
+    game_time.update_with_instant(*now);
+    *time = game_time.clone();
+}
+
+// Server that tweaks the best donut so far, one layer at a time
+pub struct SimulatedServer {
+    rng: StdRng,
+    attempts: HashMap<Entity, Attempts>,
+    cooked: bool,
+    waiting: bool,
+}
+
+impl SimulatedServer {
+    // Seeded separately, so the customers are the same as in the windowed game
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            attempts: HashMap::new(),
+            cooked: false,
+            waiting: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.attempts.clear();
+        self.new_day();
+    }
+
+    // Remembers what the regulars liked, but not the offer in progress
+    fn new_day(&mut self) {
+        self.cooked = false;
+        self.waiting = false;
+    }
+}
+
+#[derive(Default)]
+struct Attempts {
+    tried: HashSet<DonutLayers>,
+    best: Option<(DonutLayers, usize)>,
+}
+
+impl Attempts {
+    fn next_donut(
+        &self,
+        ingredients: &Ingredients,
+        mood: Option<Mood>,
+        rng: &mut StdRng,
+    ) -> DonutLayers {
+        let random_choice =
+            |rng: &mut StdRng, layer: usize| rng.gen_range(0..ingredients.choices(layer).max(1));
+
+        match &self.best {
+            // Keep regulars happy, unless they want a change
+            Some((best, 5)) if mood != Some(Mood::SomethingNew) => best.clone(),
+            Some((best, _)) if !best.0.is_empty() => {
+                for _ in 0..32 {
+                    let mut donut = best.clone();
+                    let layer = rng.gen_range(0..donut.0.len());
+                    donut.0[layer] = random_choice(rng, layer);
+
+                    if !self.tried.contains(&donut) {
+                        return donut;
+                    }
+                }
+
+                DonutLayers(
+                    (0..best.0.len())
+                        .map(|layer| random_choice(rng, layer))
+                        .collect(),
+                )
+            }
+            _ => DonutLayers(
+                (0..ingredients.layers.len())
+                    .map(|layer| random_choice(rng, layer))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn simulated_server(
+    mut commands: Commands,
+    mut server: ResMut<SimulatedServer>,
+    ingredients: Res<Ingredients>,
+    mut ev_donut_offered: EventReader<DonutOfferedEvent>,
+    mut ev_offer_donut: EventWriter<OfferDonutEvent>,
+    customer: Query<(Entity, Option<&Mood>), (With<CurrentCustomer>, Without<LeavingTimer>)>,
+    cooking_donut: Query<Entity, With<CookingDonut>>,
+) {
+    let server = &mut *server;
+
+    let mut reacted = false;
+    for DonutOfferedEvent {
+        customer,
+        layers,
+        rank,
+        ..
+    } in ev_donut_offered.iter()
+    {
+        let attempts = server.attempts.entry(*customer).or_default();
+        attempts.tried.insert(layers.clone());
+        if attempts
+            .best
+            .as_ref()
+            .map_or(true, |(_, best_rank)| rank >= best_rank)
+        {
+            attempts.best = Some((layers.clone(), *rank));
+        }
+
+        server.waiting = false;
+        reacted = true;
+    }
+
+    // Give the customer a frame to start leaving
+    if reacted || server.waiting {
+        return;
+    }
+
+    if let Ok((customer, mood)) = customer.get_single() {
+        if server.cooked {
+            ev_offer_donut.send(OfferDonutEvent);
+            server.cooked = false;
+            server.waiting = true;
+        } else {
+            for cooking_donut in cooking_donut.iter() {
+                commands.entity(cooking_donut).despawn_recursive();
+            }
+
+            let layers = server.attempts.entry(customer).or_default().next_donut(
+                &ingredients,
+                mood.copied(),
+                &mut server.rng,
+            );
+
+            commands
+                .spawn_bundle(DonutBundle::new(layers))
+                .insert(CookingDonut);
+            server.cooked = true;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Simulation {
+    games: usize,
+    results: Vec<GameResult>,
+    // Donuts offered to every customer in the current game
+    offers: HashMap<Entity, usize>,
+}
+
+struct GameResult {
+    won: bool,
+    days: u32,
+    offers: usize,
+    customers: usize,
+}
+
+fn record_offers(
+    mut simulation: ResMut<Simulation>,
+    mut ev_donut_offered: EventReader<DonutOfferedEvent>,
+) {
+    for DonutOfferedEvent { customer, .. } in ev_donut_offered.iter() {
+        *simulation.offers.entry(*customer).or_default() += 1;
+    }
+}
+
+fn skip_day_summary(mut server: ResMut<SimulatedServer>, mut app_state: ResMut<State<AppState>>) {
+    server.new_day();
+    app_state.pop().ok();
+}
+
+fn finish_game(
+    mut simulation: ResMut<Simulation>,
+    mut server: ResMut<SimulatedServer>,
+    day: Res<Day>,
+    mut app_state: ResMut<State<AppState>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let offers = std::mem::take(&mut simulation.offers);
+    let result = GameResult {
+        won: *app_state.current() == AppState::GameOver,
+        days: day.number,
+        offers: offers.values().sum(),
+        customers: offers.len(),
+    };
+
+    println!(
+        "Game {}: {} on day {} after {} donuts offered to {} customers ({:.1} per customer)",
+        simulation.results.len() + 1,
+        if result.won { "won" } else { "lost" },
+        result.days,
+        result.offers,
+        result.customers,
+        result.offers as f32 / result.customers.max(1) as f32
+    );
+
+    simulation.results.push(result);
+    server.reset();
+
+    if simulation.results.len() < simulation.games {
+        app_state.set(AppState::InGame).ok();
+        return;
+    }
+
+    let wins = simulation
+        .results
+        .iter()
+        .filter(|result| result.won)
+        .count();
+    let offers = simulation
+        .results
+        .iter()
+        .filter(|result| result.won)
+        .map(|result| result.offers)
+        .collect::<Vec<_>>();
+    let all_offers: usize = simulation.results.iter().map(|result| result.offers).sum();
+    let customers: usize = simulation
+        .results
+        .iter()
+        .map(|result| result.customers)
+        .sum();
+
+    println!("Played {} games, won {}", simulation.results.len(), wins);
+    println!(
+        "Turns to win: {:.1} on average, {} at best, {} at worst",
+        offers.iter().sum::<usize>() as f32 / wins.max(1) as f32,
+        offers.iter().min().unwrap_or(&0),
+        offers.iter().max().unwrap_or(&0)
+    );
+    println!(
+        "Donuts offered per customer: {:.1}",
+        all_offers as f32 / customers.max(1) as f32
+    );
+
+    app_exit.send(AppExit);
+}
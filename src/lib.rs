@@ -0,0 +1,293 @@
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+use bevy_common_assets::json::JsonAssetPlugin;
+use bevy_ninepatch::*;
+
+pub mod alignment;
+pub mod assets;
+pub mod component;
+pub mod difficulty;
+pub mod export;
+pub mod headless;
+pub mod save;
+pub mod settings;
+pub mod system;
+pub mod taste;
+
+static PHOTO: &str = "photo";
+static NEXT_CUSTOMER: &str = "next_customer";
+static WINNING: &str = "winning";
+
+// Game rules without any rendering. The app adds the `AppState` state itself,
+// starting wherever its presentation layer needs
+pub struct DonutGamePlugin;
+
+impl Plugin for DonutGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<component::GameRng>()
+            .init_resource::<difficulty::Difficulty>()
+            .add_plugin(CustomersPlugin)
+            .add_plugin(CookingPlugin)
+            .add_plugin(DaysPlugin)
+            .add_plugin(GameOverPlugin);
+    }
+}
+
+// Line of customers coming to the booth
+pub struct CustomersPlugin;
+
+impl Plugin for CustomersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::InGame).with_system(system::setup_session),
+        )
+        .add_system_set(
+            // The line changes in the same order every frame, so a seed gives the same customers
+            SystemSet::on_update(AppState::InGame)
+                .with_system(system::next_customer.label(NEXT_CUSTOMER))
+                .with_system(system::fill_line.after(NEXT_CUSTOMER))
+                .with_system(system::leaving.before(NEXT_CUSTOMER)),
+        );
+    }
+}
+
+// Cooking donuts and offering them to the current customer
+pub struct CookingPlugin;
+
+impl Plugin for CookingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<component::OfferDonutEvent>()
+            .add_event::<component::DonutOfferedEvent>()
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(system::change_cooking_donut)
+                    .with_system(system::change_cooking_donut_buttons)
+                    .with_system(system::cook_another_donut)
+                    .with_system(system::offer_cooked_donut),
+            );
+    }
+}
+
+// Shifts with a clock, and the regulars coming back the next day
+pub struct DaysPlugin;
+
+impl Plugin for DaysPlugin {
+    fn build(&self, app: &mut App) {
+        // Winning on the last tick of the last day still wins
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame).with_system(system::shift_clock.after(WINNING)),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::DayOver).with_system(system::next_day));
+    }
+}
+
+// Winning or losing the game and starting over
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(system::winning.label(WINNING))
+                .with_system(system::losing.after(WINNING)),
+        );
+
+        for state in AppState::GAME_OVER {
+            app.add_system_set(
+                SystemSet::on_exit(state)
+                    .with_system(system::cleanup)
+                    .with_system(system::end_session)
+                    .with_system(system::clear_events::<component::OfferDonutEvent>)
+                    .with_system(system::clear_events::<component::DonutOfferedEvent>),
+            );
+        }
+    }
+}
+
+// Window contents: assets, menus, sprites, UI and the transaction log,
+// with the session saved on exit
+pub struct DonutPresentationPlugin;
+
+impl Plugin for DonutPresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<assets::TextureAtlasData>()
+            .add_asset_loader(assets::TextureAtlasLoader)
+            .add_plugin(JsonAssetPlugin::<assets::IngredientCatalog>::new(&[
+                "catalog.json",
+            ]))
+            .add_plugin(JsonAssetPlugin::<assets::AlignmentTable>::new(&[
+                "alignment.json",
+            ]))
+            .add_plugin(NinePatchPlugin::<()>::default())
+            .add_loading_state(
+                LoadingState::new(AppState::AssetLoading)
+                    .continue_to_state(AppState::MainMenu)
+                    .with_collection::<assets::MyAssets>(),
+            )
+            .add_state(AppState::AssetLoading)
+            .add_system_set(
+                SystemSet::on_update(AppState::AssetLoading)
+                    .with_system(assets::check_loading::<assets::MyAssets>.exclusive_system()),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::AssetLoading).with_system(assets::init))
+            .insert_resource(settings::load())
+            .add_system(settings::apply_settings)
+            // Edited assets, when the asset server watches for changes, and custom difficulty
+            .add_system(assets::reload_assets)
+            .add_system(difficulty::reload_custom)
+            .add_system_set(
+                // Also coming back from a paused game
+                SystemSet::on_enter(AppState::MainMenu)
+                    .with_system(system::cleanup)
+                    .with_system(system::end_session)
+                    .with_system(system::clear_events::<component::OfferDonutEvent>)
+                    .with_system(system::clear_events::<component::DonutOfferedEvent>)
+                    .with_system(system::clear_events::<component::PhotosTakenEvent>)
+                    .with_system(system::setup_main_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(system::check_assets)
+                    .with_system(system::main_menu_buttons)
+                    .with_system(system::difficulty_buttons)
+                    .with_system(system::settings_buttons)
+                    .with_system(system::quit_button),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(system::cleanup))
+            .add_system_set(
+                SystemSet::on_enter(AppState::AssetError).with_system(system::setup_asset_error),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::AssetError).with_system(system::quit_button),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(system::setup_game))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(system::pause_game)
+                    .with_system(system::update_cash_text)
+                    .with_system(system::update_clock_text)
+                    .with_system(system::update_mood_text),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Paused).with_system(system::setup_pause_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Paused)
+                    .with_system(system::pause_menu_buttons)
+                    .with_system(system::settings_buttons)
+                    .with_system(system::quit_button),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused)
+                    .with_system(system::despawn_with::<component::PauseMenu>),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::DayOver).with_system(system::setup_day_summary),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::DayOver).with_system(system::next_day_button),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::DayOver)
+                    .with_system(system::despawn_with::<component::DaySummary>),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(PHOTO)
+                    .with_system(system::add_donut_sprites)
+                    .with_system(system::update_donut_sprites)
+                    .with_system(system::dress_customers),
+            )
+            .add_plugin(TransactionLogPlugin)
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame).with_system(export::export_history),
+            )
+            .add_system_to_stage(CoreStage::Last, save::save_on_exit);
+
+        for state in AppState::GAME_OVER {
+            app.add_system_set(
+                SystemSet::on_enter(state.clone())
+                    .with_system(system::setup_game_over)
+                    .with_system(save::delete_save),
+            )
+            .add_system_set(
+                SystemSet::on_update(state)
+                    .with_system(system::play_again_button)
+                    .with_system(export::export_history),
+            );
+        }
+
+        // Only for tuning the assets, not in release builds
+        if cfg!(debug_assertions) {
+            app.add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(alignment::open_alignment_view),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Alignment)
+                    .with_system(alignment::setup_alignment_view),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Alignment).with_system(alignment::alignment_view),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Alignment).with_system(system::cleanup));
+        }
+    }
+}
+
+// Photos of every offered donut and the reaction to it, for everyone or the current customer
+pub struct TransactionLogPlugin;
+
+impl Plugin for TransactionLogPlugin {
+    fn build(&self, app: &mut App) {
+        for state in AppState::GAME_OVER {
+            app.add_system_set(
+                SystemSet::on_exit(state)
+                    .with_system(system::clear_events::<component::PhotosTakenEvent>),
+            );
+        }
+
+        app.add_event::<component::PhotosTakenEvent>()
+            .init_resource::<component::NotesMode>()
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .label(PHOTO)
+                    .with_system(system::log_transaction),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .after(PHOTO)
+                    .with_system(system::take_photos)
+                    .with_system(system::filter_notes)
+                    .with_system(system::disappearing),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame).with_system(system::toggle_notes),
+            )
+            .add_system(system::mouse_scroll);
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum AppState {
+    AssetLoading,
+    // Assets loaded, but some are missing or broken
+    AssetError,
+    MainMenu,
+    // Debug view for tuning the alignment table, opened from the main menu of debug builds
+    Alignment,
+    InGame,
+    // Pushed on top of `InGame`, which freezes until it's popped
+    Paused,
+    // Between two days, pushed on top of `InGame` like `Paused`
+    DayOver,
+    // Won with enough regulars
+    GameOver,
+    // Lost too many customers
+    GameLost,
+}
+
+impl AppState {
+    // Both ways a game can end
+    pub const GAME_OVER: [AppState; 2] = [AppState::GameOver, AppState::GameLost];
+}
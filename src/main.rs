@@ -1,18 +1,34 @@
+use bevy::asset::AssetServerSettings;
 use bevy::prelude::*;
-use bevy_asset_loader::prelude::*;
-use bevy_common_assets::json::JsonAssetPlugin;
 use bevy_inspector_egui::WorldInspectorPlugin;
-use bevy_ninepatch::*;
-
-mod assets;
-mod component;
-mod system;
+use donut_tell_me::{
+    component::GameRng, difficulty::Difficulty, headless, DonutGamePlugin, DonutPresentationPlugin,
+};
 
 fn main() {
-    static PHOTO: &str = "photo";
+    let seed = game_seed();
+    println!("Game seed: {}", seed);
+
+    let difficulty = arg_value("--difficulty")
+        .and_then(|name| Difficulty::from_name(&name))
+        .unwrap_or_default();
+
+    if std::env::args().any(|arg| arg == "--headless") {
+        let games = arg_value("--games")
+            .and_then(|games| games.parse().ok())
+            .unwrap_or(1);
+        headless::run(seed, games, difficulty);
+        return;
+    }
+
     let mut app = App::new();
 
-    app.insert_resource(WindowDescriptor {
+    app.insert_resource(AssetServerSettings {
+        // Edited assets are re-applied while the game runs, when developing
+        watch_for_changes: cfg!(debug_assertions),
+        ..Default::default()
+    })
+    .insert_resource(WindowDescriptor {
         width: 720.,
         height: 590., // UI looks the best at this size
         title: "Donut Tell Me!".to_string(),
@@ -20,43 +36,10 @@ fn main() {
     })
     .add_plugins(DefaultPlugins)
     .insert_resource(ClearColor(Color::hex("86c0d1").unwrap_or(Color::WHITE)))
-    .add_plugin(JsonAssetPlugin::<assets::TextureAtlasData>::new(&[
-        "atlas.json",
-    ]))
-    .add_plugin(NinePatchPlugin::<()>::default())
-    .add_event::<component::PhotosTakenEvent>()
-    .add_loading_state(
-        LoadingState::new(AppState::AssetLoading)
-            .continue_to_state(AppState::InGame)
-            .with_collection::<assets::MyAssets>(),
-    )
-    .add_state(AppState::AssetLoading)
-    .add_system_set(SystemSet::on_exit(AppState::AssetLoading).with_system(assets::init))
-    .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(system::setup_game))
-    .add_system_set(
-        SystemSet::on_update(AppState::InGame)
-            .label(PHOTO)
-            .with_system(system::log_transaction),
-    )
-    .add_system_set(
-        SystemSet::on_update(AppState::InGame)
-            .after(PHOTO)
-            .with_system(system::add_donut_sprites)
-            .with_system(system::change_cooking_donut)
-            .with_system(system::change_cooking_donut_buttons)
-            .with_system(system::update_donut_sprites)
-            .with_system(system::cook_another_donut)
-            .with_system(system::offer_cooked_donut)
-            .with_system(system::next_customer)
-            .with_system(system::fill_line)
-            .with_system(system::winning),
-    )
-    .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(system::setup_game_over))
-    .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(system::play_again_button))
-    .add_system_set(SystemSet::on_exit(AppState::GameOver).with_system(system::cleanup))
-    .add_system(system::disappearing)
-    .add_system(system::leaving)
-    .add_system(system::mouse_scroll);
+    .insert_resource(GameRng::new(seed))
+    .insert_resource(difficulty)
+    .add_plugin(DonutPresentationPlugin)
+    .add_plugin(DonutGamePlugin);
 
     if cfg!(debug_assertions) {
         app.add_plugin(WorldInspectorPlugin::new());
@@ -65,9 +48,18 @@ fn main() {
     app.run();
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
-pub enum AppState {
-    AssetLoading,
-    InGame,
-    GameOver,
+// Taken from `--seed <number>` or the DONUT_SEED environment variable, random otherwise
+fn game_seed() -> u64 {
+    arg_value("--seed")
+        .or_else(|| std::env::var("DONUT_SEED").ok())
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random)
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let args = std::env::args().collect::<Vec<_>>();
+
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1).cloned())
 }
@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::component::*;
+use crate::difficulty::Difficulty;
+use crate::taste::Taste;
+use crate::AppState;
+
+// Shop session as it was when the game was closed
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    // In the order they stand in line, the current customer first
+    pub customers: Vec<SavedCustomer>,
+    pub next_customer_id: u32,
+    pub last_used_donut: Option<DonutLayers>,
+    pub history: Vec<Transaction>,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub started_at: u64,
+    #[serde(default)]
+    pub lost_customers: usize,
+    #[serde(default)]
+    pub cash: Option<i32>,
+    #[serde(default)]
+    pub day: Option<u32>,
+    // Seconds into the day
+    #[serde(default)]
+    pub day_elapsed: f32,
+    #[serde(default)]
+    pub hints_left: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedCustomer {
+    pub id: CustomerId,
+    pub taste: Taste,
+    pub appearance: Appearance,
+    pub regular: bool,
+    // Missing from older saves
+    #[serde(default)]
+    pub patience: Option<u32>,
+    #[serde(default)]
+    pub loyalty: Option<u32>,
+    #[serde(default)]
+    pub favorite: Option<DonutLayers>,
+    #[serde(default)]
+    pub mood: Option<Mood>,
+}
+
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir().unwrap_or_default().join("donut_tell_me")
+}
+
+fn save_path() -> PathBuf {
+    data_dir().join("save.json")
+}
+
+pub fn load() -> Option<SaveGame> {
+    let bytes = fs::read(save_path()).ok()?;
+
+    match serde_json::from_slice(&bytes) {
+        Ok(save) => Some(save),
+        Err(err) => {
+            warn!("Ignoring broken save {}: {}", save_path().display(), err);
+            None
+        }
+    }
+}
+
+pub fn store(save: &SaveGame) -> io::Result<()> {
+    fs::create_dir_all(data_dir())?;
+    fs::write(save_path(), serde_json::to_vec_pretty(save)?)
+}
+
+// Everything that goes into a save
+#[derive(SystemParam)]
+pub struct SaveData<'w, 's> {
+    line: Option<Res<'w, Line>>,
+    customers: Query<
+        'w,
+        's,
+        (
+            &'static CustomerId,
+            &'static Taste,
+            &'static Appearance,
+            Option<&'static Regular>,
+            Option<&'static Patience>,
+            Option<&'static Loyalty>,
+            Option<&'static Favorite>,
+            Option<&'static Mood>,
+        ),
+    >,
+    next_customer_id: Option<Res<'w, NextCustomerId>>,
+    last_used_donut: Option<Res<'w, LastUsedDonut>>,
+    history: Option<Res<'w, TransactionHistory>>,
+    difficulty: Res<'w, Difficulty>,
+    session: Option<Res<'w, Session>>,
+    lost_customers: Option<Res<'w, LostCustomers>>,
+    cash: Option<Res<'w, Cash>>,
+    day: Option<Res<'w, Day>>,
+    hints_left: Option<Res<'w, HintsLeft>>,
+}
+
+impl<'w, 's> SaveData<'w, 's> {
+    pub fn snapshot(&self) -> Option<SaveGame> {
+        let line = self.line.as_ref()?;
+
+        Some(SaveGame {
+            customers: line
+                .0
+                .iter()
+                .filter_map(|customer| self.customers.get(*customer).ok())
+                .map(
+                    |(id, taste, appearance, regular, patience, loyalty, favorite, mood)| {
+                        SavedCustomer {
+                            id: *id,
+                            taste: taste.clone(),
+                            appearance: *appearance,
+                            regular: regular.is_some(),
+                            patience: patience.map(|patience| patience.0),
+                            loyalty: loyalty.map(|loyalty| loyalty.0),
+                            favorite: favorite.map(|favorite| favorite.0.clone()),
+                            mood: mood.copied(),
+                        }
+                    },
+                )
+                .collect(),
+            next_customer_id: self.next_customer_id.as_ref().map(|id| id.0).unwrap_or(0),
+            last_used_donut: self
+                .last_used_donut
+                .as_ref()
+                .map(|donut| donut.layers.clone()),
+            history: self
+                .history
+                .as_ref()
+                .map(|history| history.0.clone())
+                .unwrap_or_default(),
+            difficulty: self.difficulty.clone(),
+            started_at: self
+                .session
+                .as_ref()
+                .map_or(0, |session| session.started_at),
+            lost_customers: self.lost_customers.as_ref().map_or(0, |lost| lost.0),
+            cash: self.cash.as_ref().map(|cash| cash.0),
+            day: self.day.as_ref().map(|day| day.number),
+            day_elapsed: self.day.as_ref().map_or(0., |day| day.clock.elapsed_secs()),
+            hints_left: self.hints_left.as_ref().map(|hints| hints.0),
+        })
+    }
+
+    pub fn save(&self) {
+        if let Some(save) = self.snapshot() {
+            match store(&save) {
+                Ok(()) => info!("Saved the game to {}", save_path().display()),
+                Err(err) => warn!("Failed to save the game: {}", err),
+            }
+        }
+    }
+}
+
+pub fn save_on_exit(
+    mut ev_app_exit: EventReader<AppExit>,
+    app_state: Res<State<AppState>>,
+    save_data: SaveData,
+) {
+    // The game may be paused
+    let in_game = *app_state.current() == AppState::InGame
+        || app_state.inactives().contains(&AppState::InGame);
+    if ev_app_exit.iter().next().is_none() || !in_game {
+        return;
+    }
+
+    save_data.save();
+}
+
+// A finished game can't be continued
+pub fn delete_save() {
+    fs::remove_file(save_path()).ok();
+}
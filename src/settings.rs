@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use serde::{Deserialize, Serialize};
+
+use crate::save;
+
+// Player preferences, kept between runs
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub fullscreen: bool,
+}
+
+fn settings_path() -> PathBuf {
+    save::data_dir().join("settings.json")
+}
+
+pub fn load() -> Settings {
+    fs::read(settings_path())
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+pub fn store(settings: &Settings) {
+    let result = fs::create_dir_all(save::data_dir())
+        .and_then(|_| fs::write(settings_path(), serde_json::to_vec_pretty(settings)?));
+    if let Err(err) = result {
+        warn!("Failed to store the settings: {}", err);
+    }
+}
+
+pub fn apply_settings(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_mode(if settings.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        });
+    }
+}
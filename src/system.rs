@@ -1,8 +1,14 @@
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use crate::assets::*;
 use crate::component::*;
+use crate::difficulty::*;
+use crate::save::{self, SaveGame};
+use crate::settings::{self, Settings};
+use crate::taste::*;
 use crate::AppState;
+use bevy::app::AppExit;
 use bevy::core_pipeline::clear_color::ClearColorConfig;
 use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
@@ -13,10 +19,115 @@ use bevy::sprite::Anchor;
 use bevy_ninepatch::*;
 use rand::prelude::*;
 
+// Starts a new session, or resumes the saved one when continuing
+pub fn setup_session(
+    mut commands: Commands,
+    save: Option<Res<SaveGame>>,
+    difficulty: Res<Difficulty>,
+) {
+    let save = match save {
+        Some(save) => save,
+        None => {
+            commands.insert_resource(Session {
+                started_at: unix_timestamp(),
+            });
+            commands.insert_resource(Line(VecDeque::new()));
+            commands.insert_resource(NextCustomerId(0));
+            commands.insert_resource(TransactionHistory::default());
+            commands.insert_resource(LostCustomers(0));
+            commands.insert_resource(Cash(difficulty.starting_cash));
+            commands.insert_resource(Day::new(1, difficulty.day_length));
+            commands.insert_resource(HintsLeft(difficulty.hints_per_day));
+            return;
+        }
+    };
+
+    let mut line = VecDeque::new();
+    for customer in save.customers.iter() {
+        let entity = spawn_customer(
+            &mut commands,
+            customer.id,
+            customer.taste.clone(),
+            Patience(customer.patience.unwrap_or(save.difficulty.patience)),
+            customer.appearance,
+        );
+        if customer.regular {
+            commands.entity(entity).insert(Regular);
+        }
+        // Older saves only know who was a regular
+        let loyalty = match (customer.loyalty, customer.regular) {
+            (Some(loyalty), _) => loyalty,
+            (None, true) => Loyalty::MAX,
+            (None, false) => 0,
+        };
+        commands.entity(entity).insert(Loyalty(loyalty));
+        if let Some(favorite) = &customer.favorite {
+            commands.entity(entity).insert(Favorite(favorite.clone()));
+        }
+        if let Some(mood) = customer.mood {
+            commands.entity(entity).insert(mood);
+        }
+        line.push_back(entity);
+    }
+
+    // Resume serving the customer who was at the booth
+    if let Some(current_customer) = line.front() {
+        commands
+            .entity(*current_customer)
+            .insert(CurrentCustomer)
+            .insert(Visibility { is_visible: true });
+    }
+
+    commands.insert_resource(Session {
+        started_at: save.started_at,
+    });
+    commands.insert_resource(Line(line));
+    commands.insert_resource(NextCustomerId(save.next_customer_id));
+    commands.insert_resource(TransactionHistory(save.history.clone()));
+    commands.insert_resource(LostCustomers(save.lost_customers));
+    commands.insert_resource(Cash(save.cash.unwrap_or(save.difficulty.starting_cash)));
+    let mut day = Day::new(save.day.unwrap_or(1), save.difficulty.day_length);
+    day.clock
+        .set_elapsed(Duration::from_secs_f32(save.day_elapsed));
+    commands.insert_resource(day);
+    commands.insert_resource(HintsLeft(
+        save.hints_left.unwrap_or(save.difficulty.hints_per_day),
+    ));
+    commands.insert_resource(save.difficulty.clone());
+    if let Some(layers) = &save.last_used_donut {
+        commands.insert_resource(LastUsedDonut {
+            layers: layers.clone(),
+        });
+    }
+
+    commands.remove_resource::<SaveGame>();
+}
+
+// Forgets the finished session, so that the next one starts from scratch.
+// Its entities are despawned by `cleanup`
+pub fn end_session(mut commands: Commands) {
+    commands.remove_resource::<Session>();
+    commands.remove_resource::<Line>();
+    commands.remove_resource::<NextCustomerId>();
+    commands.remove_resource::<LastUsedDonut>();
+    commands.remove_resource::<TransactionHistory>();
+    commands.remove_resource::<LostCustomers>();
+    commands.remove_resource::<Cash>();
+    commands.remove_resource::<Day>();
+    commands.remove_resource::<HintsLeft>();
+}
+
+// Drops events that the finished session didn't get to handle
+pub fn clear_events<T: Send + Sync + 'static>(mut events: ResMut<Events<T>>) {
+    events.clear();
+}
+
 pub fn setup_game(
     mut commands: Commands,
     my_assets: Res<MyAssets>,
+    ingredients: Res<Ingredients>,
     mut nine_patches: ResMut<Assets<NinePatchBuilder<()>>>,
+    mut images: ResMut<Assets<Image>>,
 ) {
     let mut main_camera_bundle = Camera2dBundle::default();
     main_camera_bundle.transform.translation.x = 100.; // half of the side panel width
@@ -24,7 +135,25 @@ pub fn setup_game(
         .spawn_bundle(main_camera_bundle)
         .insert(RenderLayers::from_layers(&[0, 1]));
 
-    commands.insert_resource(Line(VecDeque::new()));
+    // Portrait camera, looking at the current customer only
+    let portrait_image_handle = new_render_target(&mut images, 256);
+    let mut portrait_camera_bundle = Camera2dBundle {
+        camera_2d: Camera2d {
+            clear_color: ClearColorConfig::None,
+        },
+        camera: Camera {
+            target: RenderTarget::Image(portrait_image_handle.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    portrait_camera_bundle.transform.translation.y = 150.;
+    portrait_camera_bundle.transform.scale = Vec3::ONE * 0.8;
+    commands
+        .spawn_bundle(portrait_camera_bundle)
+        .insert(PortraitCamera)
+        .insert(UiCameraConfig { show_ui: false })
+        .insert(RenderLayers::layer(2));
 
     let nine_patch_handle = nine_patches.add(NinePatchBuilder::by_margins(44, 44, 44, 44));
 
@@ -86,101 +215,19 @@ pub fn setup_game(
                             ..Default::default()
                         })
                         .with_children(|parent| {
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(BaseLeftButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "< base".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
-
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
+                            for (layer, Layer { label, .. }) in
+                                ingredients.layers.iter().enumerate()
+                            {
+                                spawn_wood_button(
+                                    parent,
+                                    &my_assets,
+                                    &format!("< {}", label),
+                                    LayerButton {
+                                        layer,
+                                        cycle: Cycle::Left,
                                     },
-                                    ..Default::default()
-                                })
-                                .insert(GlazingLeftButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "< glazing".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
-
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(SprinklesLeftButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "< top".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
+                                );
+                            }
                         });
 
                     // Center placeholder
@@ -202,67 +249,8 @@ pub fn setup_game(
                             ..Default::default()
                         })
                         .with_children(|parent| {
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(OfferButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "^ offer".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
-
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(NewDonutButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "new donut".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
+                            spawn_wood_button(parent, &my_assets, "^ offer", OfferButton);
+                            spawn_wood_button(parent, &my_assets, "new donut", NewDonutButton);
                         });
 
                     // Right buttons
@@ -285,100 +273,19 @@ pub fn setup_game(
                             ..Default::default()
                         })
                         .with_children(|parent| {
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(BaseRightButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "base >".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
-
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
+                            for (layer, Layer { label, .. }) in
+                                ingredients.layers.iter().enumerate()
+                            {
+                                spawn_wood_button(
+                                    parent,
+                                    &my_assets,
+                                    &format!("{} >", label),
+                                    LayerButton {
+                                        layer,
+                                        cycle: Cycle::Right,
                                     },
-                                    ..Default::default()
-                                })
-                                .insert(GlazingRightButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "glazing >".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
-
-                            parent
-                                .spawn_bundle(ButtonBundle {
-                                    image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
-                                    style: Style {
-                                        padding: UiRect::all(Val::Px(10.)),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(SprinklesRightButton)
-                                .with_children(|parent| {
-                                    parent.spawn_bundle(TextBundle {
-                                        text: Text {
-                                            sections: vec![TextSection {
-                                                value: "top >".to_string(),
-                                                style: TextStyle {
-                                                    font_size: 20.,
-                                                    font: my_assets.font_pixel.clone(),
-                                                    color: Color::BLACK,
-                                                },
-                                            }],
-                                            alignment: TextAlignment::CENTER,
-                                        },
-                                        style: Style {
-                                            margin: UiRect::all(Val::Auto),
-
-                                            ..Default::default()
-                                        },
-                                        ..Default::default()
-                                    });
-                                });
+                                );
+                            }
                         });
                 });
 
@@ -417,20 +324,92 @@ pub fn setup_game(
                         ..Default::default()
                     });
 
-                    // Moving panel
+                    // Header
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::ColumnReverse,
+                                align_items: AlignItems::Center,
+                                flex_shrink: 0.,
+                                padding: UiRect::new(
+                                    Val::Px(20.),
+                                    Val::Px(20.),
+                                    Val::Px(20.),
+                                    Val::Px(0.),
+                                ),
+                                ..default()
+                            },
+                            color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            spawn_wood_button(parent, &my_assets, "notes [tab]", NotesButton);
+
+                            parent
+                                .spawn_bundle(TextBundle {
+                                    text: Text {
+                                        sections: vec![TextSection {
+                                            value: String::new(),
+                                            style: TextStyle {
+                                                font_size: 16.,
+                                                font: my_assets.font_pixel.clone(),
+                                                color: Color::WHITE,
+                                            },
+                                        }],
+                                        alignment: TextAlignment::CENTER,
+                                    },
+                                    style: Style {
+                                        margin: UiRect::all(Val::Px(5.)),
+                                        ..default()
+                                    },
+                                    ..default()
+                                })
+                                .insert(NotesTitle);
+
+                            parent
+                                .spawn_bundle(ImageBundle {
+                                    image: UiImage(portrait_image_handle),
+                                    style: Style {
+                                        size: Size {
+                                            width: Val::Px(80.),
+                                            height: Val::Undefined,
+                                        },
+                                        aspect_ratio: Some(1.),
+                                        ..Default::default()
+                                    },
+                                    ..Default::default()
+                                })
+                                .insert(Portrait);
+                        });
+
+                    // Window of the moving panel, so that it doesn't scroll over the header
                     parent
                         .spawn_bundle(NodeBundle {
                             style: Style {
                                 flex_direction: FlexDirection::ColumnReverse,
                                 flex_grow: 1.0,
-                                max_size: Size::new(Val::Undefined, Val::Undefined),
+                                overflow: Overflow::Hidden,
                                 ..default()
                             },
                             color: Color::NONE.into(),
                             ..default()
                         })
-                        .insert(ScrollingList::default())
-                        .insert(TransactionLog);
+                        .with_children(|parent| {
+                            // Moving panel
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        flex_direction: FlexDirection::ColumnReverse,
+                                        flex_grow: 1.0,
+                                        max_size: Size::new(Val::Undefined, Val::Undefined),
+                                        ..default()
+                                    },
+                                    color: Color::NONE.into(),
+                                    ..default()
+                                })
+                                .insert(ScrollingList::default())
+                                .insert(TransactionLog);
+                        });
                 });
         });
 
@@ -440,6 +419,164 @@ pub fn setup_game(
         transform: Transform::from_translation(Vec3::new(0., -150., 0.)).with_scale(Vec3::ONE * 2.),
         ..Default::default()
     });
+
+    // Cash, filled in by `update_cash_text`
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 30.,
+                    font: my_assets.font_blocks.clone(),
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.),
+                    top: Val::Px(20.),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(CashText);
+
+    // Day and time left, filled in by `update_clock_text`
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.,
+                    font: my_assets.font_pixel.clone(),
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.),
+                    top: Val::Px(60.),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(ClockText);
+
+    // Filled in by `update_mood_text`
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.,
+                    font: my_assets.font_pixel.clone(),
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.),
+                    top: Val::Px(90.),
+                    ..default()
+                },
+                max_size: Size::new(Val::Px(300.), Val::Undefined),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(MoodText);
+}
+
+pub fn update_cash_text(cash: Res<Cash>, mut texts: Query<&mut Text, With<CashText>>) {
+    for mut text in texts.iter_mut() {
+        // Also fill in texts spawned after the last change
+        if cash.is_changed() || text.sections[0].value.is_empty() {
+            text.sections[0].value = format!("${}", cash.0);
+        }
+    }
+}
+
+pub fn update_clock_text(day: Res<Day>, mut texts: Query<&mut Text, With<ClockText>>) {
+    let seconds_left = day.seconds_left().ceil() as u32;
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "Day {}  {}:{:02}",
+            day.number,
+            seconds_left / 60,
+            seconds_left % 60
+        );
+    }
+}
+
+pub fn update_mood_text(
+    customer: Query<(&Loyalty, Option<&Mood>, Option<&Favorite>), With<CurrentCustomer>>,
+    ingredients: Res<Ingredients>,
+    mut texts: Query<&mut Text, With<MoodText>>,
+) {
+    let value = match customer.get_single() {
+        Ok((loyalty, mood, favorite)) => {
+            let mood = match (mood, favorite) {
+                (Some(Mood::TheUsual), Some(Favorite(favorite))) => {
+                    format!("\nThe usual: {}", ingredients.describe(favorite))
+                }
+                (Some(Mood::SomethingNew), _) => "\nWants something new".to_string(),
+                _ => String::new(),
+            };
+            format!("Loyalty {}/{}{}", loyalty.0, Loyalty::MAX, mood)
+        }
+        Err(_) => String::new(),
+    };
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn spawn_wood_button(
+    parent: &mut ChildBuilder,
+    my_assets: &MyAssets,
+    label: &str,
+    marker: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            image: UiImage(my_assets.ui_button_rectangle_wood.clone()),
+            style: Style {
+                padding: UiRect::all(Val::Px(10.)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value: label.to_string(),
+                        style: TextStyle {
+                            font_size: 20.,
+                            font: my_assets.font_pixel.clone(),
+                            color: Color::BLACK,
+                        },
+                    }],
+                    alignment: TextAlignment::CENTER,
+                },
+                style: Style {
+                    margin: UiRect::all(Val::Auto),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        });
 }
 
 #[derive(Component, Default)]
@@ -471,96 +608,53 @@ pub fn mouse_scroll(
     }
 }
 
-#[allow(clippy::type_complexity)]
+// Keys cycling each layer left and right, top to bottom
+pub const LAYER_KEYS: [(KeyCode, KeyCode); 6] = [
+    (KeyCode::Q, KeyCode::W),
+    (KeyCode::A, KeyCode::S),
+    (KeyCode::Z, KeyCode::X),
+    (KeyCode::E, KeyCode::R),
+    (KeyCode::D, KeyCode::F),
+    (KeyCode::C, KeyCode::V),
+];
+
 pub fn change_cooking_donut(
     keys: Res<Input<KeyCode>>,
-    mut cooking_donut: Query<
-        (&mut Base, &mut Glazing, &mut Sprinkles),
-        (With<CookingDonut>, With<Donut>),
-    >,
+    ingredients: Res<Ingredients>,
+    mut cooking_donut: Query<&mut DonutLayers, (With<CookingDonut>, With<Donut>)>,
 ) {
-    for (mut base, mut glazing, mut sprinkles) in cooking_donut.iter_mut() {
+    for mut donut in cooking_donut.iter_mut() {
         if keys.just_pressed(KeyCode::Right) {
-            base.cycle_right();
+            donut.cycle_right(0, &ingredients);
         }
         if keys.just_pressed(KeyCode::Left) {
-            base.cycle_left();
-        }
-        if keys.just_pressed(KeyCode::W) {
-            base.cycle_right();
-        }
-        if keys.just_pressed(KeyCode::Q) {
-            base.cycle_left();
+            donut.cycle_left(0, &ingredients);
         }
 
-        if keys.just_pressed(KeyCode::S) {
-            glazing.cycle_right();
-        }
-        if keys.just_pressed(KeyCode::A) {
-            glazing.cycle_left();
-        }
-
-        if keys.just_pressed(KeyCode::X) {
-            sprinkles.cycle_right();
-        }
-        if keys.just_pressed(KeyCode::Z) {
-            sprinkles.cycle_left();
+        for (layer, (left, right)) in LAYER_KEYS.iter().enumerate() {
+            if keys.just_pressed(*right) {
+                donut.cycle_right(layer, &ingredients);
+            }
+            if keys.just_pressed(*left) {
+                donut.cycle_left(layer, &ingredients);
+            }
         }
     }
 }
 
-#[allow(clippy::type_complexity)]
 pub fn change_cooking_donut_buttons(
-    mut cooking_donut: Query<
-        (&mut Base, &mut Glazing, &mut Sprinkles),
-        (With<CookingDonut>, With<Donut>),
-    >,
-    mut set: ParamSet<(
-        Query<&mut Interaction, With<BaseLeftButton>>,
-        Query<&mut Interaction, With<BaseRightButton>>,
-        Query<&mut Interaction, With<GlazingLeftButton>>,
-        Query<&mut Interaction, With<GlazingRightButton>>,
-        Query<&mut Interaction, With<SprinklesLeftButton>>,
-        Query<&mut Interaction, With<SprinklesRightButton>>,
-    )>,
+    mut cooking_donut: Query<&mut DonutLayers, (With<CookingDonut>, With<Donut>)>,
+    ingredients: Res<Ingredients>,
+    mut buttons: Query<(&mut Interaction, &LayerButton)>,
 ) {
-    for (mut base, mut glazing, mut sprinkles) in cooking_donut.iter_mut() {
-        for mut base_left in set.p0().iter_mut() {
-            if let Interaction::Clicked = *base_left {
-                base.cycle_left();
-                *base_left = Interaction::None;
-            }
-        }
-        for mut base_right in set.p1().iter_mut() {
-            if let Interaction::Clicked = *base_right {
-                base.cycle_right();
-                *base_right = Interaction::None;
-            }
-        }
-
-        for mut glazing_left in set.p2().iter_mut() {
-            if let Interaction::Clicked = *glazing_left {
-                glazing.cycle_left();
-                *glazing_left = Interaction::None;
-            }
-        }
-        for mut glazing_right in set.p3().iter_mut() {
-            if let Interaction::Clicked = *glazing_right {
-                glazing.cycle_right();
-                *glazing_right = Interaction::None;
-            }
-        }
-
-        for mut sprinkles_left in set.p4().iter_mut() {
-            if let Interaction::Clicked = *sprinkles_left {
-                sprinkles.cycle_left();
-                *sprinkles_left = Interaction::None;
-            }
-        }
-        for mut sprinkles_right in set.p5().iter_mut() {
-            if let Interaction::Clicked = *sprinkles_right {
-                sprinkles.cycle_right();
-                *sprinkles_right = Interaction::None;
+    for mut donut in cooking_donut.iter_mut() {
+        for (mut interaction, button) in buttons.iter_mut() {
+            if let Interaction::Clicked = *interaction {
+                match button.cycle {
+                    Cycle::Left => donut.cycle_left(button.layer, &ingredients),
+                    Cycle::Right => donut.cycle_right(button.layer, &ingredients),
+                };
+                *interaction = Interaction::None;
             }
         }
     }
@@ -568,80 +662,47 @@ pub fn change_cooking_donut_buttons(
 
 pub fn add_donut_sprites(
     mut commands: Commands,
-    added_donuts: Query<(Entity, &Base, &Glazing, &Sprinkles), Added<Donut>>,
-    atlases: Res<Atlases>,
+    added_donuts: Query<(Entity, &DonutLayers), Added<Donut>>,
+    atlases: Res<AtlasRegistry>,
+    ingredients: Res<Ingredients>,
 ) {
-    for (entity, base, glazing, sprinkles) in added_donuts.iter() {
+    for (entity, donut) in added_donuts.iter() {
         commands.entity(entity).with_children(|parent| {
-            parent
-                .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlases.donuts_atlas.clone(),
-                    sprite: TextureAtlasSprite {
-                        index: base.to_sprite_index(),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(*base)
-                .insert(RenderLayers::layer(1));
-
-            parent
-                .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlases.donuts_atlas.clone(),
-                    sprite: TextureAtlasSprite {
-                        index: glazing.to_sprite_index(),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(*glazing)
-                .insert(RenderLayers::layer(1));
-
-            parent
-                .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlases.donuts_atlas.clone(),
-                    sprite: TextureAtlasSprite {
-                        index: sprinkles.to_sprite_index(),
+            for (layer, choice) in donut.0.iter().enumerate() {
+                parent
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: atlases.donuts.handle.clone(),
+                        sprite: TextureAtlasSprite {
+                            index: ingredients.sprite_index(layer, *choice),
+                            ..Default::default()
+                        },
+                        // Keep layers stacked in order
+                        transform: Transform::from_xyz(0., 0., layer as f32 * 0.01),
                         ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(*sprinkles)
-                .insert(RenderLayers::layer(1));
+                    })
+                    .insert(DonutLayerSprite(layer))
+                    .insert(RenderLayers::layer(1));
+            }
         });
     }
 }
 
-#[allow(clippy::type_complexity)]
 pub fn update_donut_sprites(
-    changed_donuts: Query<
-        (&Children, &Base, &Glazing, &Sprinkles),
-        (
-            With<Donut>,
-            Or<(Changed<Base>, Changed<Glazing>, Changed<Sprinkles>)>,
-        ),
-    >,
-    mut set: ParamSet<(
-        Query<(&mut Base, &mut TextureAtlasSprite), Without<Donut>>,
-        Query<(&mut Glazing, &mut TextureAtlasSprite), Without<Donut>>,
-        Query<(&mut Sprinkles, &mut TextureAtlasSprite), Without<Donut>>,
-    )>,
+    donuts: Query<(&Children, &DonutLayers, ChangeTrackers<DonutLayers>), With<Donut>>,
+    mut sprites: Query<(&DonutLayerSprite, &mut TextureAtlasSprite)>,
+    ingredients: Res<Ingredients>,
 ) {
-    for (children, parent_base, parent_glazing, parent_sprinkles) in changed_donuts.iter() {
-        for &child in children.iter() {
-            if let Ok((mut child_base, mut sprite)) = set.p0().get_mut(child) {
-                *child_base = *parent_base;
-                sprite.index = child_base.to_sprite_index();
-            }
-
-            if let Ok((mut child_glazing, mut sprite)) = set.p1().get_mut(child) {
-                *child_glazing = *parent_glazing;
-                sprite.index = child_glazing.to_sprite_index();
-            }
+    for (children, donut, donut_tracker) in donuts.iter() {
+        // Also when the ingredient catalog was reloaded
+        if !donut_tracker.is_changed() && !ingredients.is_changed() {
+            continue;
+        }
 
-            if let Ok((mut child_sprinkles, mut sprite)) = set.p2().get_mut(child) {
-                *child_sprinkles = *parent_sprinkles;
-                sprite.index = child_sprinkles.to_sprite_index();
+        for &child in children.iter() {
+            if let Ok((DonutLayerSprite(layer), mut sprite)) = sprites.get_mut(child) {
+                if let Some(choice) = donut.0.get(*layer) {
+                    sprite.index = ingredients.sprite_index(*layer, *choice);
+                }
             }
         }
     }
@@ -652,6 +713,7 @@ pub fn cook_another_donut(
     keys: Res<Input<KeyCode>>,
     mut interactions: Query<&mut Interaction, With<NewDonutButton>>,
     last_used_donut: Option<Res<LastUsedDonut>>,
+    ingredients: Res<Ingredients>,
     cooking_donut: Query<Entity, With<CookingDonut>>,
 ) {
     let mut do_stuff = || {
@@ -659,14 +721,14 @@ pub fn cook_another_donut(
             commands.entity(cooking_donut).despawn_recursive();
         }
 
-        let mut new_donut = DonutBundle::new();
-        if let Some(last_used_donut) = &last_used_donut {
-            new_donut.base = last_used_donut.base;
-            new_donut.glazing = last_used_donut.glazing;
-            new_donut.sprinkles = last_used_donut.sprinkles;
-        }
+        let layers = match &last_used_donut {
+            Some(last_used_donut) => last_used_donut.layers.clone(),
+            None => DonutLayers::new(&ingredients),
+        };
 
-        commands.spawn_bundle(new_donut).insert(CookingDonut);
+        commands
+            .spawn_bundle(DonutBundle::new(layers))
+            .insert(CookingDonut);
     };
 
     for mut interaction in interactions.iter_mut() {
@@ -686,23 +748,45 @@ pub fn offer_cooked_donut(
     mut commands: Commands,
     keys: Res<Input<KeyCode>>,
     mut interactions: Query<&mut Interaction, With<OfferButton>>,
-    cooking_donut: Query<(Entity, &Base, &Glazing, &Sprinkles), With<CookingDonut>>,
-    customer: Query<(Entity, &Taste), With<CurrentCustomer>>,
-    photo_cameras: Query<Entity, With<PhotoCamera>>,
-    atlases: Res<Atlases>,
-    mut ev_photos_taken: EventWriter<PhotosTakenEvent>,
-    mut images: ResMut<Assets<Image>>,
+    mut ev_offer_donut: EventReader<OfferDonutEvent>,
+    cooking_donut: Query<(Entity, &DonutLayers), With<CookingDonut>>,
+    mut customer: Query<
+        (
+            Entity,
+            &CustomerId,
+            &Taste,
+            &mut Patience,
+            &mut Loyalty,
+            Option<&Mood>,
+        ),
+        With<CurrentCustomer>,
+    >,
+    mut history: ResMut<TransactionHistory>,
+    mut cash: ResMut<Cash>,
+    mut hints_left: ResMut<HintsLeft>,
+    day: Res<Day>,
+    ingredients: Res<Ingredients>,
+    difficulty: Res<Difficulty>,
+    mut ev_donut_offered: EventWriter<DonutOfferedEvent>,
 ) {
     let mut do_stuff = || {
-        if let Ok((customer, taste)) = customer.get_single() {
-            if let Ok((cooking_donut, base, glazing, sprinkles)) = cooking_donut.get_single() {
+        if let Ok((customer, id, taste, mut patience, mut loyalty, mood)) =
+            customer.get_single_mut()
+        {
+            if let Ok((cooking_donut, layers)) = cooking_donut.get_single() {
                 commands.insert_resource(LastUsedDonut {
-                    base: *base,
-                    glazing: *glazing,
-                    sprinkles: *sprinkles,
+                    layers: layers.clone(),
                 });
 
-                let donut_rank = taste.rank(base, glazing, sprinkles);
+                let mut donut_rank = taste.rank(layers);
+
+                let repeat = history.0.iter().any(|transaction| {
+                    transaction.customer == *id && transaction.layers == *layers
+                });
+                let wants_something_new = mood == Some(&Mood::SomethingNew) && repeat;
+                if wants_something_new {
+                    donut_rank = donut_rank.saturating_sub(2);
+                }
 
                 let emotion = match donut_rank {
                     5 => Emo::Love,
@@ -713,95 +797,61 @@ pub fn offer_cooked_donut(
                 };
 
                 if emotion == Emo::Love {
+                    commands.entity(customer).insert(Favorite(layers.clone()));
+                }
+
+                loyalty.change(emotion);
+                if loyalty.is_regular() {
                     commands.entity(customer).insert(Regular);
                 } else {
                     commands.entity(customer).remove::<Regular>();
                 }
 
-                commands
-                    .entity(customer)
-                    .insert(LeavingTimer(Timer::from_seconds(2.1, false)));
-
-                for photo_camera in photo_cameras.iter() {
-                    commands.entity(photo_camera).despawn_recursive();
-                }
-
-                let size = Extent3d {
-                    width: 512,
-                    height: 512,
-                    ..default()
-                };
-                // This is the texture that will be rendered to.
-                let mut image = Image::default();
-                image.texture_descriptor.size = size;
-                image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
-                    | TextureUsages::COPY_DST
-                    | TextureUsages::RENDER_ATTACHMENT;
-
-                // fill image.data with zeroes
-                image.resize(size);
-
-                let donut_image_handle = images.add(image.clone());
-                let emo_image_handle = images.add(image);
-
-                let donut_camera_bundle = Camera2dBundle {
-                    camera_2d: Camera2d {
-                        clear_color: ClearColorConfig::None,
-                    },
-                    camera: Camera {
-                        target: RenderTarget::Image(donut_image_handle.clone()),
-                        ..Default::default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(0., -150., 1.))
-                        .with_scale(Vec3::ONE * 0.3),
-                    ..Default::default()
+                // Nothing to improve on a donut they only mind for being the same again
+                let hint = if hints_left.0 > 0 && !wants_something_new {
+                    Hint::find(taste, &ingredients, layers)
+                } else {
+                    None
                 };
-                commands
-                    .spawn_bundle(donut_camera_bundle)
-                    .insert(PhotoCamera)
-                    .insert(UiCameraConfig { show_ui: false })
-                    .insert(RenderLayers::layer(1));
-
-                commands
-                    .entity(cooking_donut)
-                    .insert(Photo(donut_image_handle));
+                if hint.is_some() {
+                    hints_left.0 -= 1;
+                }
 
-                // Emo camera
-                let emo_camera_bundle = Camera2dBundle {
-                    camera_2d: Camera2d {
-                        clear_color: ClearColorConfig::None,
-                    },
-                    camera: Camera {
-                        target: RenderTarget::Image(emo_image_handle.clone()),
-                        ..Default::default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(0., 180., 10.))
-                        .with_scale(Vec3::ONE * 0.5),
-                    ..Default::default()
-                };
-                commands
-                    .spawn_bundle(emo_camera_bundle)
-                    .insert(PhotoCamera)
-                    .insert(UiCameraConfig { show_ui: false });
+                patience.0 = patience.0.saturating_sub(match emotion {
+                    Emo::Love | Emo::Happy => 0,
+                    Emo::Sad | Emo::Angry => 1,
+                    Emo::Heartbroken => 2,
+                });
 
                 commands
-                    .spawn_bundle(SpriteSheetBundle {
-                        texture_atlas: atlases.emotes_atlas.clone(),
-                        sprite: TextureAtlasSprite {
-                            index: emotion as usize,
-                            ..Default::default()
-                        },
-                        transform: Transform::from_translation(Vec3::new(100., 245., 0.))
-                            .with_scale(Vec3::ONE * 1.5),
-                        ..Default::default()
-                    })
-                    .insert(emotion)
-                    .insert(Photo(emo_image_handle))
-                    .insert(DisappearingTimer(Timer::from_seconds(2., false)));
+                    .entity(customer)
+                    .insert(LeavingTimer(Timer::from_seconds(2.1, false)));
 
-                println!("I rate this donut as {}", "⭐️".repeat(donut_rank));
+                let revenue = donut_rank as i32 * difficulty.price_per_star;
+                let cost = ingredients.cost(layers);
+                cash.0 += revenue - cost;
+
+                history.0.push(Transaction {
+                    customer: *id,
+                    layers: layers.clone(),
+                    rank: donut_rank,
+                    emotion,
+                    timestamp: unix_timestamp(),
+                    day: day.number,
+                    hint: hint.clone(),
+                    revenue,
+                    cost,
+                });
 
-                ev_photos_taken.send(PhotosTakenEvent);
+                ev_donut_offered.send(DonutOfferedEvent {
+                    customer,
+                    donut: cooking_donut,
+                    layers: layers.clone(),
+                    rank: donut_rank,
+                    emotion,
+                    hint,
+                    transaction: history.0.len() - 1,
+                });
             }
         }
     };
@@ -816,17 +866,198 @@ pub fn offer_cooked_donut(
             *interaction = Interaction::None
         }
     }
+
+    for _event in ev_offer_donut.iter() {
+        do_stuff();
+    }
+}
+
+// Square texture for a camera to render to
+fn new_render_target(images: &mut Assets<Image>, side: u32) -> Handle<Image> {
+    let size = Extent3d {
+        width: side,
+        height: side,
+        ..default()
+    };
+    let mut image = Image::default();
+    image.texture_descriptor.size = size;
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+
+    // fill image.data with zeroes
+    image.resize(size);
+
+    images.add(image)
+}
+
+// Renders the offered donut and the customer's reaction for the log
+pub fn take_photos(
+    mut commands: Commands,
+    mut ev_donut_offered: EventReader<DonutOfferedEvent>,
+    photo_cameras: Query<Entity, With<PhotoCamera>>,
+    atlases: Res<AtlasRegistry>,
+    mut ev_photos_taken: EventWriter<PhotosTakenEvent>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for DonutOfferedEvent {
+        donut,
+        emotion,
+        hint,
+        transaction,
+        ..
+    } in ev_donut_offered.iter()
+    {
+        for photo_camera in photo_cameras.iter() {
+            commands.entity(photo_camera).despawn_recursive();
+        }
+
+        let donut_image_handle = new_render_target(&mut images, 512);
+        let emo_image_handle = new_render_target(&mut images, 512);
+
+        let donut_camera_bundle = Camera2dBundle {
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            camera: Camera {
+                target: RenderTarget::Image(donut_image_handle.clone()),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(Vec3::new(0., -150., 1.))
+                .with_scale(Vec3::ONE * 0.3),
+            ..Default::default()
+        };
+        commands
+            .spawn_bundle(donut_camera_bundle)
+            .insert(PhotoCamera)
+            .insert(UiCameraConfig { show_ui: false })
+            .insert(RenderLayers::layer(1));
+
+        commands.entity(*donut).insert(Photo(donut_image_handle));
+
+        // Emo camera
+        let emo_camera_bundle = Camera2dBundle {
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            camera: Camera {
+                target: RenderTarget::Image(emo_image_handle.clone()),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(Vec3::new(0., 180., 10.))
+                .with_scale(Vec3::ONE * 0.5),
+            ..Default::default()
+        };
+        commands
+            .spawn_bundle(emo_camera_bundle)
+            .insert(PhotoCamera)
+            .insert(UiCameraConfig { show_ui: false });
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: atlases.emotes.handle.clone(),
+                sprite: atlases.emotes.sprite(emotion.sprite()),
+                transform: Transform::from_translation(Vec3::new(100., 245., 0.))
+                    .with_scale(Vec3::ONE * 1.5),
+                ..Default::default()
+            })
+            .insert(*emotion)
+            .insert(Photo(emo_image_handle))
+            .insert(DisappearingTimer(Timer::from_seconds(2., false)));
+
+        // Next to the reaction, so it's in the same photo
+        if let Some(hint) = hint {
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: atlases.emotes.handle.clone(),
+                    sprite: atlases.emotes.sprite(hint.emote()),
+                    transform: Transform::from_translation(Vec3::new(50., 245., 0.))
+                        .with_scale(Vec3::ONE * 1.5),
+                    ..Default::default()
+                })
+                .insert(DisappearingTimer(Timer::from_seconds(2., false)));
+        }
+
+        ev_photos_taken.send(PhotosTakenEvent {
+            transaction: *transaction,
+        });
+    }
 }
 
+// Builds the log from the transaction history and attaches the photos once they're rendered
+#[allow(clippy::too_many_arguments)]
 pub fn log_transaction(
     mut commands: Commands,
+    session: Res<Session>,
+    history: Res<TransactionHistory>,
+    ingredients: Res<Ingredients>,
+    my_assets: Res<MyAssets>,
+    mut entries: Local<Vec<Entity>>,
     mut ev_photos_taken: EventReader<PhotosTakenEvent>,
     photo_cameras: Query<Entity, With<PhotoCamera>>,
     cooking_donut: Query<(Entity, &Photo), With<CookingDonut>>,
     emo_photo: Query<&Photo, With<Emo>>,
     log: Query<Entity, With<TransactionLog>>,
 ) {
-    for _event in ev_photos_taken.iter() {
+    if session.is_changed() {
+        entries.clear();
+    }
+
+    if let Ok(log) = log.get_single() {
+        for (index, transaction) in history.0.iter().enumerate().skip(entries.len()) {
+            let new_entry = commands
+                .spawn_bundle(NodeBundle {
+                    color: Color::NONE.into(),
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        flex_shrink: 0.,
+                        padding: UiRect::all(Val::Px(20.)),
+                        ..default()
+                    },
+                    ..Default::default()
+                })
+                .insert(LogEntry {
+                    transaction: index,
+                    customer: transaction.customer,
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: match &transaction.hint {
+                                    Some(hint) => format!(
+                                        "{} {}\n{}",
+                                        "*".repeat(transaction.rank),
+                                        ingredients.describe(&transaction.layers),
+                                        hint.describe(&ingredients)
+                                    ),
+                                    None => format!(
+                                        "{} {}",
+                                        "*".repeat(transaction.rank),
+                                        ingredients.describe(&transaction.layers)
+                                    ),
+                                },
+                                style: TextStyle {
+                                    font_size: 16.,
+                                    font: my_assets.font_pixel.clone(),
+                                    color: Color::WHITE,
+                                },
+                            }],
+                            alignment: TextAlignment::CENTER_LEFT,
+                        },
+                        style: Style {
+                            size: Size::new(Val::Px(160.), Val::Auto),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+                })
+                .id();
+            commands.entity(log).insert_children(0, &[new_entry]);
+            entries.push(new_entry);
+        }
+    }
+
+    for PhotosTakenEvent { transaction } in ev_photos_taken.iter() {
         if let Ok((cooking_donut, photo)) = cooking_donut.get_single() {
             if let Ok(emo_photo) = emo_photo.get_single() {
                 for photo_camera in photo_cameras.iter() {
@@ -834,57 +1065,109 @@ pub fn log_transaction(
                 }
                 commands.entity(cooking_donut).despawn_recursive();
 
-                for log in log.iter() {
-                    let new_entry = commands
-                        .spawn_bundle(NodeBundle {
-                            color: Color::NONE.into(),
-                            style: Style {
-                                flex_shrink: 0.,
-                                padding: UiRect::all(Val::Px(20.)),
-
-                                ..default()
-                            },
-                            ..Default::default()
-                        })
-                        .with_children(|parent| {
-                            parent
-                                .spawn_bundle(ImageBundle {
-                                    image: UiImage(photo.0.clone()),
-                                    style: Style {
-                                        size: Size {
-                                            width: Val::Px(80.),
-                                            height: Val::Undefined,
-                                        },
-                                        aspect_ratio: Some(1.),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(Node::default());
-
-                            parent
-                                .spawn_bundle(ImageBundle {
-                                    image: UiImage(emo_photo.0.clone()),
-                                    style: Style {
-                                        size: Size {
-                                            width: Val::Px(80.),
-                                            height: Val::Undefined,
-                                        },
-                                        aspect_ratio: Some(1.),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                })
-                                .insert(Node::default());
-                        })
-                        .id();
-                    commands.entity(log).insert_children(0, &[new_entry]);
+                if let Some(entry) = entries.get(*transaction) {
+                    commands.entity(*entry).with_children(|parent| {
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                color: Color::NONE.into(),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                for image in [&photo.0, &emo_photo.0] {
+                                    parent
+                                        .spawn_bundle(ImageBundle {
+                                            image: UiImage(image.clone()),
+                                            style: Style {
+                                                size: Size {
+                                                    width: Val::Px(80.),
+                                                    height: Val::Undefined,
+                                                },
+                                                aspect_ratio: Some(1.),
+                                                ..Default::default()
+                                            },
+                                            ..Default::default()
+                                        })
+                                        .insert(Node::default());
+                                }
+                            });
+                    });
                 }
             }
         }
     }
 }
 
+pub fn toggle_notes(
+    keys: Res<Input<KeyCode>>,
+    mut interactions: Query<&mut Interaction, With<NotesButton>>,
+    mut mode: ResMut<NotesMode>,
+) {
+    let mut toggle = || {
+        *mode = match *mode {
+            NotesMode::Everyone => NotesMode::CurrentCustomer,
+            NotesMode::CurrentCustomer => NotesMode::Everyone,
+        }
+    };
+
+    if keys.just_pressed(KeyCode::Tab) {
+        toggle();
+    }
+
+    for mut interaction in interactions.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            toggle();
+            *interaction = Interaction::None;
+        }
+    }
+}
+
+// Hides the entries of other customers when only the current customer's notes are shown
+pub fn filter_notes(
+    mode: Res<NotesMode>,
+    current_customer: Query<&CustomerId, With<CurrentCustomer>>,
+    mut entries: Query<(&LogEntry, &mut Style)>,
+    mut portrait: Query<&mut Style, (With<Portrait>, Without<LogEntry>)>,
+    mut title: Query<&mut Text, With<NotesTitle>>,
+    mut lists: Query<(&mut ScrollingList, &mut Style), (Without<Portrait>, Without<LogEntry>)>,
+) {
+    let current_customer = current_customer.get_single().ok();
+
+    for (entry, mut style) in entries.iter_mut() {
+        let shown = match *mode {
+            NotesMode::Everyone => true,
+            NotesMode::CurrentCustomer => current_customer == Some(&entry.customer),
+        };
+        let display = if shown { Display::Flex } else { Display::None };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    let (display, label) = match *mode {
+        NotesMode::Everyone => (Display::None, "Everyone"),
+        NotesMode::CurrentCustomer => (Display::Flex, "This customer"),
+    };
+
+    for mut style in portrait.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    for mut text in title.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.to_string();
+        }
+    }
+
+    if mode.is_changed() {
+        for (mut list, mut style) in lists.iter_mut() {
+            list.position = 0.;
+            style.position.top = Val::Px(0.);
+        }
+    }
+}
+
 pub fn disappearing(
     mut commands: Commands,
     mut timers: Query<(Entity, &mut DisappearingTimer)>,
@@ -897,147 +1180,769 @@ pub fn disappearing(
     }
 }
 
+// Sends the served customer to the back of the line, or away for good once they run out of patience
 pub fn leaving(
     mut commands: Commands,
-    mut timers: Query<(Entity, &mut LeavingTimer)>,
+    mut timers: Query<(Entity, &mut LeavingTimer, &Patience)>,
+    mut line: ResMut<Line>,
+    mut lost_customers: ResMut<LostCustomers>,
     time: Res<Time>,
 ) {
-    for (entity, mut timer) in timers.iter_mut() {
+    for (entity, mut timer, patience) in timers.iter_mut() {
         if timer.0.tick(time.delta()).just_finished() {
-            commands
-                .entity(entity)
-                .remove::<CurrentCustomer>()
-                .insert(Visibility { is_visible: false });
+            line.0.retain(|customer| *customer != entity);
+
+            if patience.0 == 0 {
+                commands.entity(entity).despawn_recursive();
+                lost_customers.0 += 1;
+            } else {
+                line.0.push_back(entity);
+                commands
+                    .entity(entity)
+                    .remove::<CurrentCustomer>()
+                    .remove::<LeavingTimer>()
+                    .remove::<Mood>()
+                    .insert(Visibility { is_visible: false });
+            }
         }
     }
 }
 
-pub fn winning(regulars: Query<Entity, With<Regular>>, mut app_state: ResMut<State<AppState>>) {
-    if regulars.iter().count() >= 3 {
-        app_state.set(AppState::GameOver).ok();
-    }
+pub fn losing(
+    lost_customers: Res<LostCustomers>,
+    difficulty: Res<Difficulty>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if lost_customers.0 >= difficulty.customers_to_lose {
+        app_state.set(AppState::GameLost).ok();
+    }
 }
 
-pub fn setup_game_over(
+pub fn winning(
+    regulars: Query<Entity, With<Regular>>,
+    cash: Res<Cash>,
+    difficulty: Res<Difficulty>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if regulars.iter().count() >= difficulty.regulars_to_win && cash.0 >= difficulty.cash_to_win {
+        app_state.set(AppState::GameOver).ok();
+    }
+}
+
+// Ends the day when its clock runs out, or the game after the last one
+pub fn shift_clock(
+    time: Res<Time>,
+    mut day: ResMut<Day>,
+    difficulty: Res<Difficulty>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if day.clock.tick(time.delta()).just_finished() {
+        if day.number >= difficulty.days {
+            app_state.set(AppState::GameLost).ok();
+        } else {
+            app_state.push(AppState::DayOver).ok();
+        }
+    }
+}
+
+// Opens the shop again: regulars come back with the same tastes and patience
+// restored, while everyone else is replaced by new customers
+pub fn next_day(
     mut commands: Commands,
-    my_assets: Res<MyAssets>,
-    layout: Query<Entity, With<Layout>>,
+    mut day: ResMut<Day>,
+    mut hints_left: ResMut<HintsLeft>,
+    mut line: ResMut<Line>,
+    mut customers: Query<(Entity, Option<&Regular>, &mut Patience)>,
+    difficulty: Res<Difficulty>,
 ) {
-    let layout = layout.get_single().unwrap();
+    *day = Day::new(day.number + 1, difficulty.day_length);
+    hints_left.0 = difficulty.hints_per_day;
 
-    let popup = commands.spawn_bundle(NodeBundle {
-        style: Style {
-                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
-                position_type: PositionType::Absolute,
-                ..default()
+    for (entity, regular, mut patience) in customers.iter_mut() {
+        if regular.is_some() {
+            patience.0 = difficulty.patience;
+            commands
+                .entity(entity)
+                .remove::<CurrentCustomer>()
+                .remove::<LeavingTimer>()
+                .remove::<Mood>()
+                .insert(Visibility { is_visible: false });
+        } else {
+            line.0.retain(|customer| *customer != entity);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_day_summary(
+    mut commands: Commands,
+    my_assets: Res<MyAssets>,
+    settings: Res<Settings>,
+    day: Res<Day>,
+    history: Res<TransactionHistory>,
+    regulars: Query<Entity, With<Regular>>,
+    cash: Res<Cash>,
+    difficulty: Res<Difficulty>,
+) {
+    let summary = Summary::of(
+        history
+            .0
+            .iter()
+            .filter(|transaction| transaction.day == day.number),
+    );
+    let summary = format!(
+        "{} donuts sold, {} of them loved\nProfit: ${}\nCash: ${} of ${}\nRegulars: {} of {}\nDays left: {}",
+        summary.offered,
+        summary.loved,
+        summary.profit(),
+        cash.0,
+        difficulty.cash_to_win,
+        regulars.iter().count(),
+        difficulty.regulars_to_win,
+        difficulty.days.saturating_sub(day.number)
+    );
+
+    let title = format!("Day {} is over", day.number);
+    let menu = spawn_menu(&mut commands, &my_assets, &title, &settings, |parent| {
+        parent.spawn_bundle(TextBundle {
+            text: Text {
+                sections: vec![TextSection {
+                    value: summary,
+                    style: TextStyle {
+                        font_size: 20.,
+                        font: my_assets.font_pixel.clone(),
+                        color: Color::WHITE,
+                    },
+                }],
+                alignment: TextAlignment::CENTER,
             },
-            color: Color::NONE.into(),
-            ..default()
-    }).with_children(|parent| {
-        parent
-        .spawn_bundle(NodeBundle {
             style: Style {
-                flex_direction: FlexDirection::ColumnReverse,
-                align_self: AlignSelf::Center,
-                size: Size::new(Val::Px(500.0), Val::Auto),
-                position_type: PositionType::Relative,
-                margin: UiRect::all(Val::Auto),
-                align_items: AlignItems::Center,
-                ..default()
-            },
-            color: Color::rgb(0.10, 0.10, 0.10).into(),
-            ..default()
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text {
-                    sections: vec![TextSection {
-                        value: "The game is over!".to_string(),
-                        style: TextStyle {
-                            font_size: 50.,
-                            font: my_assets.font_blocks.clone(),
-                            color: Color::WHITE,
-                        },
-                    }
-                    ],
-                    alignment: TextAlignment::CENTER,
-                },
-                style: Style {
-                    size: Size::new(Val::Px(400.), Val::Auto),
-                    ..Default::default()
-                },
+                margin: UiRect::new(Val::Auto, Val::Auto, Val::Px(0.), Val::Px(20.)),
                 ..Default::default()
-            });
+            },
+            ..Default::default()
+        });
+        spawn_menu_button(parent, &my_assets, "Next day", NextDayButton);
+    });
+    commands.entity(menu).insert(DaySummary);
+}
+
+pub fn next_day_button(
+    button: Query<&Interaction, With<NextDayButton>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    for interaction in button.iter() {
+        if let Interaction::Clicked = interaction {
+            app_state.pop().ok();
+        }
+    }
+}
+
+// Leaves the main menu as soon as it's up when the assets are broken
+pub fn check_assets(errors: Option<Res<AssetErrors>>, mut app_state: ResMut<State<AppState>>) {
+    if errors.is_some() {
+        app_state.set(AppState::AssetError).ok();
+    }
+}
 
+pub fn setup_asset_error(
+    mut commands: Commands,
+    my_assets: Res<MyAssets>,
+    settings: Res<Settings>,
+    errors: Res<AssetErrors>,
+) {
+    commands.spawn_bundle(Camera2dBundle::default());
+
+    spawn_menu(
+        &mut commands,
+        &my_assets,
+        "Broken assets",
+        &settings,
+        |parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text {
                     sections: vec![TextSection {
-                        value: "You now have enough regular customers to sustain the business. Now it's time to lay back and chill. And instead of constantly guessing other people's wants, maybe ask yourself: What do I want?".to_string(),
+                        value: errors.0.join("\n"),
                         style: TextStyle {
                             font_size: 20.,
                             font: my_assets.font_pixel.clone(),
                             color: Color::WHITE,
                         },
-                    }
-                    ],
+                    }],
                     alignment: TextAlignment::CENTER,
                 },
                 style: Style {
-                    size: Size::new(Val::Px(400.), Val::Auto),
-                    margin: UiRect::new(Val::Auto, Val::Auto, Val::Px(40.), Val::Px(40.)),
+                    margin: UiRect::new(Val::Auto, Val::Auto, Val::Px(0.), Val::Px(20.)),
+                    max_size: Size::new(Val::Px(460.), Val::Undefined),
                     ..Default::default()
                 },
                 ..Default::default()
             });
+            spawn_menu_button(parent, &my_assets, "Quit", QuitButton);
+        },
+    );
+}
+
+pub fn setup_main_menu(
+    mut commands: Commands,
+    my_assets: Res<MyAssets>,
+    difficulty: Res<Difficulty>,
+    settings: Res<Settings>,
+) {
+    commands.spawn_bundle(Camera2dBundle::default());
+
+    let can_continue = save::load().is_some();
+
+    spawn_menu(
+        &mut commands,
+        &my_assets,
+        "Donut Tell Me!",
+        &settings,
+        |parent| {
+            if can_continue {
+                spawn_menu_button(parent, &my_assets, "Continue", ContinueButton);
+            }
+            spawn_menu_button(parent, &my_assets, "New game", NewGameButton);
+
+            // Difficulty of the new game
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: UiRect::new(Val::Px(0.), Val::Px(0.), Val::Px(0.), Val::Px(40.)),
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for level in DifficultyLevel::ALL {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                color: difficulty_color(level == difficulty.level).into(),
+                                style: Style {
+                                    padding: UiRect::all(Val::Px(10.)),
+                                    margin: UiRect::all(Val::Px(5.)),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            })
+                            .insert(DifficultyButton(level))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text {
+                                        sections: vec![TextSection {
+                                            value: level.label().to_string(),
+                                            style: TextStyle {
+                                                font_size: 20.,
+                                                font: my_assets.font_blocks.clone(),
+                                                color: Color::WHITE,
+                                            },
+                                        }],
+                                        alignment: TextAlignment::CENTER,
+                                    },
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+
+            spawn_menu_button(parent, &my_assets, "Settings", SettingsButton);
+            spawn_menu_button(parent, &my_assets, "Quit", QuitButton);
+        },
+    );
+}
+
+// Dark panel in the middle of the screen, with a settings page to switch to
+fn spawn_menu(
+    commands: &mut Commands,
+    my_assets: &MyAssets,
+    title: &str,
+    settings: &Settings,
+    buttons: impl FnOnce(&mut ChildBuilder),
+) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_self: AlignSelf::Center,
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        margin: UiRect::all(Val::Auto),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: Color::rgb(0.10, 0.10, 0.10).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: title.to_string(),
+                                style: TextStyle {
+                                    font_size: 50.,
+                                    font: my_assets.font_blocks.clone(),
+                                    color: Color::WHITE,
+                                },
+                            }],
+                            alignment: TextAlignment::CENTER,
+                        },
+                        style: Style {
+                            margin: UiRect::all(Val::Px(40.)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::ColumnReverse,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .insert(MenuButtons)
+                        .with_children(buttons);
+
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::ColumnReverse,
+                                align_items: AlignItems::Center,
+                                display: Display::None,
+                                ..default()
+                            },
+                            color: Color::NONE.into(),
+                            ..default()
+                        })
+                        .insert(SettingsPanel)
+                        .with_children(|parent| {
+                            spawn_menu_button(
+                                parent,
+                                my_assets,
+                                &fullscreen_label(settings),
+                                FullscreenButton,
+                            );
+                            spawn_menu_button(parent, my_assets, "Back", SettingsBackButton);
+                        });
+                });
+        })
+        .id()
+}
+
+fn fullscreen_label(settings: &Settings) -> String {
+    format!(
+        "Fullscreen: {}",
+        if settings.fullscreen { "on" } else { "off" }
+    )
+}
+
+// Switches a menu between its own buttons and the settings page
+#[allow(clippy::type_complexity)]
+pub fn settings_buttons(
+    mut settings_button: Query<&mut Interaction, With<SettingsButton>>,
+    mut back_button: Query<&mut Interaction, (With<SettingsBackButton>, Without<SettingsButton>)>,
+    mut fullscreen_button: Query<
+        (&mut Interaction, &Children),
+        (
+            With<FullscreenButton>,
+            Without<SettingsButton>,
+            Without<SettingsBackButton>,
+        ),
+    >,
+    mut pages: Query<
+        (&mut Style, Option<&SettingsPanel>),
+        Or<(With<MenuButtons>, With<SettingsPanel>)>,
+    >,
+    mut texts: Query<&mut Text>,
+    mut settings: ResMut<Settings>,
+) {
+    let mut show_settings = None;
+
+    for mut interaction in settings_button.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            show_settings = Some(true);
+            *interaction = Interaction::None;
+        }
+    }
+
+    for mut interaction in back_button.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            show_settings = Some(false);
+            *interaction = Interaction::None;
+        }
+    }
+
+    for (mut interaction, children) in fullscreen_button.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            settings.fullscreen = !settings.fullscreen;
+            settings::store(&settings);
+            for child in children.iter() {
+                if let Ok(mut text) = texts.get_mut(*child) {
+                    text.sections[0].value = fullscreen_label(&settings);
+                }
+            }
+            *interaction = Interaction::None;
+        }
+    }
+
+    if let Some(show_settings) = show_settings {
+        for (mut style, settings_panel) in pages.iter_mut() {
+            style.display = if settings_panel.is_some() == show_settings {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+    }
+}
+
+pub fn pause_game(mut keys: ResMut<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+    if keys.just_pressed(KeyCode::Escape) {
+        // Otherwise the pause menu would see the same press and close right away
+        keys.clear_just_pressed(KeyCode::Escape);
+        app_state.push(AppState::Paused).ok();
+    }
+}
+
+pub fn setup_pause_menu(mut commands: Commands, my_assets: Res<MyAssets>, settings: Res<Settings>) {
+    let menu = spawn_menu(&mut commands, &my_assets, "Paused", &settings, |parent| {
+        spawn_menu_button(parent, &my_assets, "Resume", ResumeButton);
+        spawn_menu_button(parent, &my_assets, "Settings", SettingsButton);
+        spawn_menu_button(parent, &my_assets, "Main menu", MainMenuButton);
+        spawn_menu_button(parent, &my_assets, "Quit", QuitButton);
+    });
+    commands.entity(menu).insert(PauseMenu);
+}
+
+pub fn pause_menu_buttons(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut resume_button: Query<&mut Interaction, With<ResumeButton>>,
+    main_menu_button: Query<&Interaction, (With<MainMenuButton>, Without<ResumeButton>)>,
+    save_data: save::SaveData,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let mut resume = keys.just_pressed(KeyCode::Escape);
+
+    for mut interaction in resume_button.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            resume = true;
+            *interaction = Interaction::None;
+        }
+    }
+
+    if resume {
+        keys.clear_just_pressed(KeyCode::Escape);
+        app_state.pop().ok();
+    }
+
+    for interaction in main_menu_button.iter() {
+        if let Interaction::Clicked = interaction {
+            // So that the game can be continued from the main menu
+            save_data.save();
+            app_state.replace(AppState::MainMenu).ok();
+        }
+    }
+}
+
+pub fn quit_button(
+    button: Query<&Interaction, With<QuitButton>>,
+    mut ev_app_exit: EventWriter<AppExit>,
+) {
+    for interaction in button.iter() {
+        if let Interaction::Clicked = interaction {
+            ev_app_exit.send(AppExit);
+        }
+    }
+}
+
+pub fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
 
+fn spawn_menu_button(
+    parent: &mut ChildBuilder,
+    my_assets: &MyAssets,
+    label: &str,
+    marker: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            color: Color::TEAL.into(),
+            style: Style {
+                margin: UiRect::new(Val::Px(40.), Val::Px(40.), Val::Px(0.), Val::Px(40.)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text {
                     sections: vec![TextSection {
-                        value: "Or don't.".to_string(),
+                        value: label.to_string(),
                         style: TextStyle {
-                            font_size: 20.,
+                            font_size: 40.,
                             font: my_assets.font_blocks.clone(),
                             color: Color::WHITE,
                         },
-                    }
-                    ],
+                    }],
                     alignment: TextAlignment::CENTER,
                 },
-                ..Default::default()
-            });
-
-            parent.spawn_bundle(ButtonBundle {
-                color: Color::TEAL.into(),
                 style: Style {
-                    margin: UiRect::all(Val::Px(40.)),
+                    margin: UiRect::all(Val::Auto),
                     ..Default::default()
                 },
                 ..Default::default()
-            })
-            .insert(PlayAgainButton)
-            .with_children(|parent| {
-                parent.spawn_bundle(TextBundle {
-                    text: Text {
-                        sections: vec![TextSection {
-                            value: "Play again".to_string(),
-                            style: TextStyle {
-                                font_size: 40.,
-                                font: my_assets.font_blocks.clone(),
-                                color: Color::WHITE,
-                            },
-                        }
-                        ],
-                        alignment: TextAlignment::CENTER,
-                    },
+            });
+        });
+}
+
+fn difficulty_color(selected: bool) -> Color {
+    if selected {
+        Color::TEAL
+    } else {
+        Color::rgb(0.25, 0.25, 0.25)
+    }
+}
+
+pub fn difficulty_buttons(
+    mut buttons: Query<(&DifficultyButton, &Interaction, &mut UiColor)>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    for (DifficultyButton(level), interaction, _) in buttons.iter() {
+        if let Interaction::Clicked = interaction {
+            if *level != difficulty.level {
+                *difficulty = Difficulty::preset(*level);
+            }
+        }
+    }
+
+    if difficulty.is_changed() {
+        for (DifficultyButton(level), _, mut color) in buttons.iter_mut() {
+            *color = difficulty_color(*level == difficulty.level).into();
+        }
+    }
+}
+
+pub fn main_menu_buttons(
+    mut commands: Commands,
+    new_game_button: Query<&Interaction, With<NewGameButton>>,
+    continue_button: Query<&Interaction, With<ContinueButton>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    for interaction in new_game_button.iter() {
+        if let Interaction::Clicked = interaction {
+            save::delete_save();
+            app_state.set(AppState::InGame).ok();
+        }
+    }
+
+    for interaction in continue_button.iter() {
+        if let Interaction::Clicked = interaction {
+            if let Some(save) = save::load() {
+                commands.insert_resource(save);
+            }
+            app_state.set(AppState::InGame).ok();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_game_over(
+    mut commands: Commands,
+    my_assets: Res<MyAssets>,
+    layout: Query<Entity, With<Layout>>,
+    app_state: Res<State<AppState>>,
+    history: Option<Res<TransactionHistory>>,
+    cash: Option<Res<Cash>>,
+    lost_customers: Option<Res<LostCustomers>>,
+    difficulty: Res<Difficulty>,
+) {
+    let layout = match layout.get_single() {
+        Ok(layout) => layout,
+        Err(_) => return,
+    };
+    let walked_away = lost_customers.map_or(false, |lost| lost.0 >= difficulty.customers_to_lose);
+
+    let summary = history
+        .map(|history| Summary::of(&history.0))
+        .unwrap_or_default();
+    let summary = format!(
+        "{} donuts sold, {} of them loved\nRevenue: ${}\nIngredients: ${}\nProfit: ${}\nCash: ${}",
+        summary.offered,
+        summary.loved,
+        summary.revenue,
+        summary.costs,
+        summary.profit(),
+        cash.map_or(0, |cash| cash.0)
+    );
+
+    let (title, message) = match app_state.current() {
+        AppState::GameLost if walked_away => (
+            "Out of business!",
+            "Too many customers walked away for good, and they told all their friends about it. Maybe listen a little closer next time.",
+        ),
+        AppState::GameLost => (
+            "Out of time!",
+            "The last day is over, and the shop still can't pay its way. Maybe it just needs a few more friendly faces.",
+        ),
+        _ => (
+            "The game is over!",
+            "You now have enough regular customers and cash to sustain the business. Now it's time to lay back and chill. And instead of constantly guessing other people's wants, maybe ask yourself: What do I want?",
+        ),
+    };
+
+    let popup = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
                     style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_self: AlignSelf::Center,
+                        size: Size::new(Val::Px(500.0), Val::Auto),
+                        position_type: PositionType::Relative,
                         margin: UiRect::all(Val::Auto),
-                        ..Default::default()
+                        align_items: AlignItems::Center,
+                        ..default()
                     },
-                    ..Default::default()
+                    color: Color::rgb(0.10, 0.10, 0.10).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: title.to_string(),
+                                style: TextStyle {
+                                    font_size: 50.,
+                                    font: my_assets.font_blocks.clone(),
+                                    color: Color::WHITE,
+                                },
+                            }],
+                            alignment: TextAlignment::CENTER,
+                        },
+                        style: Style {
+                            size: Size::new(Val::Px(400.), Val::Auto),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: message.to_string(),
+                                style: TextStyle {
+                                    font_size: 20.,
+                                    font: my_assets.font_pixel.clone(),
+                                    color: Color::WHITE,
+                                },
+                            }],
+                            alignment: TextAlignment::CENTER,
+                        },
+                        style: Style {
+                            size: Size::new(Val::Px(400.), Val::Auto),
+                            margin: UiRect::new(Val::Auto, Val::Auto, Val::Px(40.), Val::Px(40.)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: summary,
+                                style: TextStyle {
+                                    font_size: 20.,
+                                    font: my_assets.font_pixel.clone(),
+                                    color: Color::WHITE,
+                                },
+                            }],
+                            alignment: TextAlignment::CENTER,
+                        },
+                        style: Style {
+                            margin: UiRect::new(Val::Auto, Val::Auto, Val::Px(0.), Val::Px(40.)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+
+                    parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: "Or don't.".to_string(),
+                                style: TextStyle {
+                                    font_size: 20.,
+                                    font: my_assets.font_blocks.clone(),
+                                    color: Color::WHITE,
+                                },
+                            }],
+                            alignment: TextAlignment::CENTER,
+                        },
+                        ..Default::default()
+                    });
+
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            color: Color::TEAL.into(),
+                            style: Style {
+                                margin: UiRect::all(Val::Px(40.)),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(PlayAgainButton)
+                        .with_children(|parent| {
+                            parent.spawn_bundle(TextBundle {
+                                text: Text {
+                                    sections: vec![TextSection {
+                                        value: "Play again".to_string(),
+                                        style: TextStyle {
+                                            font_size: 40.,
+                                            font: my_assets.font_blocks.clone(),
+                                            color: Color::WHITE,
+                                        },
+                                    }],
+                                    alignment: TextAlignment::CENTER,
+                                },
+                                style: Style {
+                                    margin: UiRect::all(Val::Auto),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            });
+                        });
+
+                    spawn_menu_button(parent, &my_assets, "Export log", ExportButton);
                 });
-            });
-        });
-    }).id();
+        })
+        .id();
 
     commands.entity(layout).push_children(&[popup]);
 }
@@ -1061,85 +1966,205 @@ pub fn cleanup(mut commands: Commands, query: Query<Entity, Without<Parent>>) {
 
 pub fn next_customer(
     mut commands: Commands,
-    mut line: ResMut<Line>,
+    mut rng: ResMut<GameRng>,
+    line: Res<Line>,
     current_customer: Query<Entity, With<CurrentCustomer>>,
+    favorites: Query<&Favorite, With<Regular>>,
+    difficulty: Res<Difficulty>,
 ) {
-    if current_customer.is_empty() && !line.0.is_empty() {
-        let current_first = line.0.pop_front().unwrap();
-        line.0.push_back(current_first);
-
-        let new_first = line.0.front().unwrap();
+    // The previous customer went to the back of the line when leaving
+    if let (true, Some(first)) = (current_customer.is_empty(), line.0.front()) {
         commands
-            .entity(*new_first)
+            .entity(*first)
             .insert(CurrentCustomer)
             .insert(Visibility { is_visible: true });
+
+        // Regulars either order their favorite again, or want to be surprised
+        if let Ok(Favorite(favorite)) = favorites.get(*first) {
+            if rng.gen_bool(difficulty.something_new_chance.clamp(0., 1.)) {
+                commands.entity(*first).insert(Mood::SomethingNew);
+            } else {
+                commands.entity(*first).insert(Mood::TheUsual);
+                commands.insert_resource(LastUsedDonut {
+                    layers: favorite.clone(),
+                });
+            }
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn fill_line(
     mut commands: Commands,
+    mut rng: ResMut<GameRng>,
     mut line: ResMut<Line>,
+    mut next_customer_id: ResMut<NextCustomerId>,
     regulars: Query<Entity, With<Regular>>,
-    atlases: Res<Atlases>,
+    ingredients: Res<Ingredients>,
+    difficulty: Res<Difficulty>,
     faces_metadata: Res<FacesMetadata>,
     hair_metadata: Res<HairMetadata>,
+    body_metadata: Res<BodyMetadata>,
 ) {
-    let mut rng = rand::thread_rng();
     let non_regulars_in_line = line
         .0
         .iter()
         .filter(|customer| !regulars.contains(**customer))
         .count();
-    if non_regulars_in_line < 4 {
-        let new_customer = commands
-            .spawn_bundle(SpatialBundle {
-                transform: Transform::from_translation(Vec3::new(0., 150., 0.)),
-                visibility: Visibility { is_visible: false },
-                ..default()
-            })
-            .insert(Taste::random())
-            .with_children(|parent| {
-                parent.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlases.skin_atlas.clone(),
-                    sprite: TextureAtlasSprite {
-                        index: 2,
-                        anchor: Anchor::Center,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                });
+    if non_regulars_in_line < difficulty.line_length {
+        let kind = TasteKind::choose(&difficulty.taste_kinds, &mut *rng);
+        let taste = Taste::random(kind, &ingredients, &difficulty.star_weights, &mut *rng);
+        let (skin, body) = match body_metadata.random(&mut *rng) {
+            Some((head, body)) => (head, Some(body)),
+            None => (
+                body_metadata.tints.first().map_or(0, |tint| tint.head),
+                None,
+            ),
+        };
+        let appearance = Appearance {
+            skin,
+            body,
+            face: faces_metadata
+                .face_indexes
+                .choose(&mut *rng)
+                .cloned()
+                .unwrap_or(0),
+            // Pick random hair style
+            hair: match hair_metadata.names.len() {
+                0 => 0,
+                styles => rng.gen_range(0..styles),
+            },
+        };
+
+        let id = CustomerId(next_customer_id.0);
+        next_customer_id.0 += 1;
+
+        let new_customer = spawn_customer(
+            &mut commands,
+            id,
+            taste,
+            Patience(difficulty.patience),
+            appearance,
+        );
+        line.0.push_back(new_customer);
+    }
+}
 
-                parent.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlases.face_atlas.clone(),
-                    transform: Transform::default().with_translation(Vec3::new(0., 0., 1.)),
-                    sprite: TextureAtlasSprite {
-                        index: faces_metadata
-                            .face_indexes
-                            .choose(&mut rng)
-                            .cloned()
-                            .unwrap_or(0),
-                        anchor: Anchor::Center,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                });
+fn spawn_customer(
+    commands: &mut Commands,
+    id: CustomerId,
+    taste: Taste,
+    patience: Patience,
+    appearance: Appearance,
+) -> Entity {
+    commands
+        .spawn_bundle(SpatialBundle {
+            transform: Transform::from_translation(Vec3::new(0., 150., 0.)),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(id)
+        .insert(taste)
+        .insert(patience)
+        .insert(Loyalty::default())
+        .insert(appearance)
+        .id()
+}
 
-                // Pick random hair style
-                let index = rng.gen_range(0..hair_metadata.names.len());
-                let anchor = hair_metadata.anchor(index);
-                parent.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlases.hair_atlas.clone(),
-                    transform: Transform::default().with_translation(Vec3::new(0., 0., 2.)),
-                    sprite: TextureAtlasSprite {
-                        index,
-                        anchor,
+pub fn dress_customers(
+    mut commands: Commands,
+    customers: Query<(Entity, &Appearance, ChangeTrackers<Appearance>)>,
+    atlases: Res<AtlasRegistry>,
+    alignment: Alignment,
+    mut ev_alignment_tables: EventReader<AssetEvent<AlignmentTable>>,
+) {
+    // Everyone again when the atlases or the alignment table were reloaded
+    let redress = atlases.is_changed() || ev_alignment_tables.iter().count() > 0;
+
+    for (entity, appearance, appearance_tracker) in customers.iter() {
+        if !redress && !appearance_tracker.is_added() {
+            continue;
+        }
+
+        commands.entity(entity).despawn_descendants();
+        // Also seen by the portrait camera
+        commands.entity(entity).with_children(|parent| {
+            let head = [
+                (&atlases.skin, appearance.skin),
+                (&atlases.face, appearance.face),
+                (&atlases.hair, appearance.hair),
+            ];
+            for (z, (atlas, index)) in head.into_iter().enumerate() {
+                // Saved before the atlas lost the sprite
+                if index >= atlas.len() {
+                    continue;
+                }
+                parent
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: atlas.handle.clone(),
+                        transform: Transform::from_xyz(0., 0., z as f32),
+                        sprite: TextureAtlasSprite {
+                            index,
+                            anchor: alignment.anchor(atlas, index).unwrap_or(Anchor::Center),
+                            ..Default::default()
+                        },
                         ..Default::default()
-                    },
-                    ..Default::default()
-                });
-            })
-            .id();
+                    })
+                    .insert(RenderLayers::from_layers(&[0, 2]));
+            }
 
-        line.0.push_back(new_customer);
+            if let Some(body) = appearance.body {
+                let parts = [
+                    (BodyPart::Neck, &atlases.skin, Some(body.neck)),
+                    (BodyPart::Shirt, &atlases.shirts, Some(body.shirt)),
+                    (BodyPart::Arm, &atlases.skin, Some(body.arm)),
+                    (BodyPart::Sleeve, &atlases.shirts, body.sleeve),
+                    (BodyPart::Hand, &atlases.skin, Some(body.hand)),
+                    (BodyPart::Hips, &atlases.pants, Some(body.hips)),
+                    (BodyPart::Leg, &atlases.pants, Some(body.leg)),
+                    (BodyPart::Shoe, &atlases.shoes, Some(body.shoe)),
+                ];
+
+                for (part, atlas, index) in parts {
+                    let (index, placement) = match (index, alignment.placement(part)) {
+                        (Some(index), Some(placement)) if index < atlas.len() => (index, placement),
+                        _ => continue,
+                    };
+                    let joint = placement.joint();
+                    let anchor = alignment
+                        .anchor(atlas, index)
+                        .unwrap_or_else(|| placement.anchor());
+                    let sides: &[bool] = if placement.both_sides {
+                        &[false, true]
+                    } else {
+                        &[false]
+                    };
+
+                    for &mirrored in sides {
+                        let (x, anchor) = if mirrored {
+                            (
+                                -joint.x,
+                                Anchor::Custom(anchor.as_vec() * Vec2::new(-1., 1.)),
+                            )
+                        } else {
+                            (joint.x, anchor.clone())
+                        };
+                        parent
+                            .spawn_bundle(SpriteSheetBundle {
+                                texture_atlas: atlas.handle.clone(),
+                                transform: Transform::from_xyz(x, joint.y, placement.z),
+                                sprite: TextureAtlasSprite {
+                                    index,
+                                    anchor,
+                                    flip_x: mirrored,
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            })
+                            .insert(RenderLayers::from_layers(&[0, 2]));
+                    }
+                }
+            }
+        });
     }
 }
@@ -0,0 +1,631 @@
+use bevy::prelude::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::component::*;
+
+// How a customer rates a donut, from 0 to 5 stars
+pub trait TasteModel {
+    fn rank(&self, donut: &DonutLayers) -> usize;
+}
+
+// How likely an ingredient is rated 1 to 5 stars, unless the difficulty says otherwise
+pub const STAR_WEIGHTS: [usize; 5] = [1, 2, 4, 6, 3];
+
+// Layer and choice of an ingredient
+pub type IngredientRef = (usize, usize);
+
+fn contains(donut: &DonutLayers, (layer, choice): IngredientRef) -> bool {
+    donut.0.get(layer) == Some(&choice)
+}
+
+// Every ingredient is rated on its own, and the donut gets the average
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Additive {
+    // Stars for every ingredient, per layer
+    pub stars: Vec<Vec<usize>>,
+}
+
+impl Additive {
+    pub fn uniform(ingredients: &Ingredients, stars: usize) -> Self {
+        Self {
+            stars: ingredients
+                .layers
+                .iter()
+                .map(|layer| vec![stars; layer.ingredients.len()])
+                .collect(),
+        }
+    }
+
+    // Rates every ingredient 1 to 5 stars, as likely as the given weights say
+    pub fn random(
+        ingredients: &Ingredients,
+        star_weights: &[usize; 5],
+        rng: &mut impl Rng,
+    ) -> Self {
+        let choices = [1, 2, 3, 4, 5];
+        let dist = WeightedIndex::new(star_weights)
+            .unwrap_or_else(|_| WeightedIndex::new(&STAR_WEIGHTS).unwrap());
+
+        Self {
+            stars: ingredients
+                .layers
+                .iter()
+                .map(|layer| {
+                    layer
+                        .ingredients
+                        .iter()
+                        .map(|_| choices[dist.sample(&mut *rng)])
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    pub fn stars(&self, (layer, choice): IngredientRef) -> usize {
+        self.stars
+            .get(layer)
+            .and_then(|layer| layer.get(choice))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn set_stars(&mut self, (layer, choice): IngredientRef, stars: usize) {
+        if let Some(rating) = self
+            .stars
+            .get_mut(layer)
+            .and_then(|layer| layer.get_mut(choice))
+        {
+            *rating = stars;
+        }
+    }
+
+    // Averages ingredient stars into donut stars
+    fn rank_stars(stars: impl ExactSizeIterator<Item = usize>) -> usize {
+        let count = stars.len();
+        if count == 0 {
+            return 0;
+        }
+
+        // Returns 0.0..1.0
+        let weight = |r| ((r as f32 - 4.) * 2. + 7.) / 9.;
+
+        let average_weight: f32 = stars.map(weight).sum::<f32>() / count as f32;
+
+        (average_weight * 5.).round() as usize
+    }
+}
+
+impl TasteModel for Additive {
+    fn rank(&self, donut: &DonutLayers) -> usize {
+        Self::rank_stars(
+            donut
+                .0
+                .iter()
+                .enumerate()
+                .map(|(layer, choice)| self.stars((layer, *choice))),
+        )
+    }
+}
+
+// An ingredient that only counts with another one, e.g. chocolate glazing only with sprinkles
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Combo {
+    pub ingredient: IngredientRef,
+    pub with: IngredientRef,
+}
+
+// Additive, except that combo ingredients are barely liked on their own
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Pairwise {
+    pub base: Additive,
+    pub combos: Vec<Combo>,
+}
+
+impl Pairwise {
+    pub fn random(
+        ingredients: &Ingredients,
+        star_weights: &[usize; 5],
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut base = Additive::random(ingredients, star_weights, rng);
+        let mut combos = vec![];
+
+        let layers: Vec<_> = (0..ingredients.layers.len())
+            .filter(|layer| ingredients.choices(*layer) > 0)
+            .collect();
+        if layers.len() >= 2 {
+            let mut picked = layers.choose_multiple(rng, 2);
+            let (first, second) = (*picked.next().unwrap(), *picked.next().unwrap());
+            let ingredient = (first, rng.gen_range(0..ingredients.choices(first)));
+            let with = (second, rng.gen_range(0..ingredients.choices(second)));
+
+            base.set_stars(ingredient, 5);
+            combos.push(Combo { ingredient, with });
+        }
+
+        Self { base, combos }
+    }
+}
+
+impl TasteModel for Pairwise {
+    fn rank(&self, donut: &DonutLayers) -> usize {
+        Additive::rank_stars(donut.0.iter().enumerate().map(|(layer, choice)| {
+            let ingredient = (layer, *choice);
+            let unpaired = self
+                .combos
+                .iter()
+                .any(|combo| combo.ingredient == ingredient && !contains(donut, combo.with));
+            if unpaired {
+                1
+            } else {
+                self.base.stars(ingredient)
+            }
+        }))
+    }
+}
+
+// Additive, except that any donut with a hated flavor gets no stars, e.g. never pink
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Veto {
+    pub base: Additive,
+    pub flavor: String,
+    pub vetoed: Vec<IngredientRef>,
+}
+
+impl Veto {
+    pub fn random(
+        ingredients: &Ingredients,
+        star_weights: &[usize; 5],
+        rng: &mut impl Rng,
+    ) -> Self {
+        let base = Additive::random(ingredients, star_weights, rng);
+
+        let mut flavors: Vec<&String> = ingredients
+            .layers
+            .iter()
+            .flat_map(|layer| layer.ingredients.iter())
+            .flat_map(|ingredient| ingredient.flavors.iter())
+            .collect();
+        flavors.sort();
+        flavors.dedup();
+        let flavor = flavors.choose(rng).map(|flavor| (*flavor).clone());
+
+        let mut vetoed = vec![];
+        for (layer, Layer { ingredients, .. }) in ingredients.layers.iter().enumerate() {
+            let hated: Vec<_> = ingredients
+                .iter()
+                .enumerate()
+                .filter(|(_, ingredient)| {
+                    flavor
+                        .as_ref()
+                        .map_or(false, |flavor| ingredient.flavors.contains(flavor))
+                })
+                .map(|(choice, _)| (layer, choice))
+                .collect();
+
+            // Keep at least one choice in every layer
+            if hated.len() < ingredients.len() {
+                vetoed.extend(hated);
+            }
+        }
+
+        Self {
+            base,
+            flavor: flavor.unwrap_or_default(),
+            vetoed,
+        }
+    }
+}
+
+impl TasteModel for Veto {
+    fn rank(&self, donut: &DonutLayers) -> usize {
+        if self.vetoed.iter().any(|vetoed| contains(donut, *vetoed)) {
+            0
+        } else {
+            self.base.rank(donut)
+        }
+    }
+}
+
+// Additive, but a donut without any of the favorites can't be more than okay
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AtLeastOne {
+    pub base: Additive,
+    pub favorites: Vec<IngredientRef>,
+}
+
+impl AtLeastOne {
+    // Best rank of a donut without favorites
+    pub const THRESHOLD: usize = 3;
+
+    pub fn random(
+        ingredients: &Ingredients,
+        star_weights: &[usize; 5],
+        rng: &mut impl Rng,
+    ) -> Self {
+        let mut base = Additive::random(ingredients, star_weights, rng);
+
+        let all: Vec<IngredientRef> = (0..ingredients.layers.len())
+            .flat_map(|layer| (0..ingredients.choices(layer)).map(move |choice| (layer, choice)))
+            .collect();
+        let count = rng.gen_range(1..=2);
+        let favorites: Vec<_> = all.choose_multiple(rng, count).cloned().collect();
+        for favorite in favorites.iter() {
+            base.set_stars(*favorite, 5);
+        }
+
+        Self { base, favorites }
+    }
+}
+
+impl TasteModel for AtLeastOne {
+    fn rank(&self, donut: &DonutLayers) -> usize {
+        let rank = self.base.rank(donut);
+        if self
+            .favorites
+            .iter()
+            .any(|favorite| contains(donut, *favorite))
+        {
+            rank
+        } else {
+            rank.min(Self::THRESHOLD)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TasteKind {
+    Additive,
+    Pairwise,
+    Veto,
+    AtLeastOne,
+}
+
+impl TasteKind {
+    pub fn choose(weights: &[(TasteKind, usize)], rng: &mut impl Rng) -> Self {
+        weights
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map_or(TasteKind::Additive, |(kind, _)| *kind)
+    }
+}
+
+// Taste of a customer, with whichever model they were given
+#[derive(Component, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Taste {
+    Additive(Additive),
+    Pairwise(Pairwise),
+    Veto(Veto),
+    AtLeastOne(AtLeastOne),
+}
+
+impl Default for Taste {
+    fn default() -> Self {
+        Taste::Additive(Additive::default())
+    }
+}
+
+impl Taste {
+    pub fn uniform(ingredients: &Ingredients, stars: usize) -> Self {
+        Taste::Additive(Additive::uniform(ingredients, stars))
+    }
+
+    pub fn all(ingredients: &Ingredients) -> Self {
+        Self::uniform(ingredients, 5)
+    }
+
+    pub fn random(
+        kind: TasteKind,
+        ingredients: &Ingredients,
+        star_weights: &[usize; 5],
+        rng: &mut impl Rng,
+    ) -> Self {
+        match kind {
+            TasteKind::Additive => {
+                Taste::Additive(Additive::random(ingredients, star_weights, rng))
+            }
+            TasteKind::Pairwise => {
+                Taste::Pairwise(Pairwise::random(ingredients, star_weights, rng))
+            }
+            TasteKind::Veto => Taste::Veto(Veto::random(ingredients, star_weights, rng)),
+            TasteKind::AtLeastOne => {
+                Taste::AtLeastOne(AtLeastOne::random(ingredients, star_weights, rng))
+            }
+        }
+    }
+
+    pub fn kind(&self) -> TasteKind {
+        match self {
+            Taste::Additive(_) => TasteKind::Additive,
+            Taste::Pairwise(_) => TasteKind::Pairwise,
+            Taste::Veto(_) => TasteKind::Veto,
+            Taste::AtLeastOne(_) => TasteKind::AtLeastOne,
+        }
+    }
+}
+
+impl TasteModel for Taste {
+    fn rank(&self, donut: &DonutLayers) -> usize {
+        match self {
+            Taste::Additive(model) => model.rank(donut),
+            Taste::Pairwise(model) => model.rank(donut),
+            Taste::Veto(model) => model.rank(donut),
+            Taste::AtLeastOne(model) => model.rank(donut),
+        }
+    }
+}
+
+// Partial clue about an offered donut, shown with an emote next to the reaction
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Hint {
+    // Another ingredient in this layer would do better
+    WorstLayer(usize),
+    // Same, and the ingredient has a flavor the better one doesn't have
+    TooMuch { layer: usize, flavor: String },
+    // Any single change makes it worse, this layer's the most
+    BestLayer(usize),
+}
+
+impl Hint {
+    // Sprites of the emotes atlas
+    const QUESTION: &'static str = "emote_question.png";
+    const DROP: &'static str = "emote_drop.png";
+    const STARS: &'static str = "emote_stars.png";
+    pub const EMOTES: [&'static str; 3] = [Self::QUESTION, Self::DROP, Self::STARS];
+
+    // Compares the donut with every donut one ingredient away, so it works with any model
+    pub fn find(
+        taste: &impl TasteModel,
+        ingredients: &Ingredients,
+        donut: &DonutLayers,
+    ) -> Option<Self> {
+        let rank = taste.rank(donut);
+        if rank >= 5 {
+            return None;
+        }
+
+        // Choice and rank of every other ingredient in the layer
+        let others = |layer: usize| {
+            (0..ingredients.choices(layer))
+                .filter(move |choice| donut.0.get(layer) != Some(choice))
+                .map(move |choice| {
+                    let mut other = donut.clone();
+                    other.0[layer] = choice;
+                    (choice, taste.rank(&other))
+                })
+        };
+
+        let worst = (0..donut.0.len())
+            .filter_map(|layer| {
+                others(layer)
+                    .max_by_key(|(_, rank)| *rank)
+                    .map(|(better, other_rank)| (layer, better, other_rank))
+            })
+            .filter(|(_, _, other_rank)| *other_rank > rank)
+            .max_by_key(|(_, _, other_rank)| *other_rank);
+
+        if let Some((layer, better, _)) = worst {
+            let better_flavors = ingredients
+                .get(layer, better)
+                .map(|better| better.flavors.as_slice())
+                .unwrap_or_default();
+            let flavor = ingredients
+                .get(layer, donut.0[layer])
+                .and_then(|current| {
+                    current
+                        .flavors
+                        .iter()
+                        .find(|flavor| !better_flavors.contains(flavor))
+                })
+                .cloned();
+
+            return Some(match flavor {
+                Some(flavor) => Hint::TooMuch { layer, flavor },
+                None => Hint::WorstLayer(layer),
+            });
+        }
+
+        (0..donut.0.len())
+            .filter_map(|layer| {
+                others(layer)
+                    .map(|(_, other_rank)| other_rank)
+                    .min()
+                    .map(|other_rank| (layer, other_rank))
+            })
+            .filter(|(_, other_rank)| *other_rank < rank)
+            .min_by_key(|(_, other_rank)| *other_rank)
+            .map(|(layer, _)| Hint::BestLayer(layer))
+    }
+
+    pub fn emote(&self) -> &'static str {
+        match self {
+            Hint::WorstLayer(_) => Self::QUESTION,
+            Hint::TooMuch { .. } => Self::DROP,
+            Hint::BestLayer(_) => Self::STARS,
+        }
+    }
+
+    pub fn describe(&self, ingredients: &Ingredients) -> String {
+        let label = |layer: &usize| {
+            ingredients
+                .layers
+                .get(*layer)
+                .map_or("???", |layer| layer.label.as_str())
+        };
+
+        match self {
+            Hint::WorstLayer(layer) => format!("Not sure about the {}", label(layer)),
+            Hint::TooMuch { flavor, .. } => format!("Less {}, please", flavor),
+            Hint::BestLayer(layer) => format!("Loved the {}", label(layer)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [TasteKind; 4] = [
+        TasteKind::Additive,
+        TasteKind::Pairwise,
+        TasteKind::Veto,
+        TasteKind::AtLeastOne,
+    ];
+
+    fn donut(choices: &[usize]) -> DonutLayers {
+        DonutLayers(choices.to_vec())
+    }
+
+    // Every possible donut made of the given ingredients
+    fn all_donuts(ingredients: &Ingredients) -> Vec<DonutLayers> {
+        let mut donuts = vec![DonutLayers::new(ingredients)];
+        for layer in 0..ingredients.layers.len() {
+            donuts = donuts
+                .into_iter()
+                .flat_map(|donut| {
+                    (0..ingredients.choices(layer)).map(move |choice| {
+                        let mut donut = donut.clone();
+                        donut.0[layer] = choice;
+                        donut
+                    })
+                })
+                .collect();
+        }
+        donuts
+    }
+
+    #[test]
+    fn test_additive_ranking() {
+        let ingredients = test_ingredients(&[3, 6, 7]);
+        let donut = DonutLayers::new(&ingredients);
+
+        assert_eq!(Taste::default().rank(&donut), 0);
+        assert_eq!(Taste::all(&ingredients).rank(&donut), 5);
+
+        let mut taste = Additive::uniform(&ingredients, 0);
+        let mut rank = |stars: [usize; 3]| {
+            for (layer, stars) in stars.into_iter().enumerate() {
+                taste.set_stars((layer, 0), stars);
+            }
+            taste.rank(&donut)
+        };
+        assert_eq!(rank([5, 5, 5]), 5);
+        assert_eq!(rank([4, 5, 5]), 5);
+        assert_eq!(rank([4, 4, 5]), 4);
+        assert_eq!(rank([2, 5, 5]), 4);
+        assert_eq!(rank([3, 4, 3]), 3);
+        assert_eq!(rank([2, 4, 4]), 3);
+        assert_eq!(rank([1, 4, 5]), 3);
+        assert_eq!(rank([1, 2, 3]), 2);
+        assert_eq!(rank([1, 2, 2]), 1);
+    }
+
+    #[test]
+    fn test_pairwise_ranking() {
+        let ingredients = test_ingredients(&[3, 6, 7]);
+        let taste = Pairwise {
+            base: Additive::uniform(&ingredients, 5),
+            combos: vec![Combo {
+                ingredient: (1, 2),
+                with: (2, 4),
+            }],
+        };
+
+        assert_eq!(taste.rank(&donut(&[0, 0, 0])), 5);
+        assert_eq!(taste.rank(&donut(&[0, 2, 4])), 5);
+        assert_eq!(taste.rank(&donut(&[0, 2, 0])), 4);
+    }
+
+    #[test]
+    fn test_veto_ranking() {
+        let mut ingredients = test_ingredients(&[3, 6, 7]);
+        ingredients.layers[1].ingredients[3].flavors = vec!["pink".to_string()];
+        let mut rng = GameRng::new(42);
+        let taste = Veto::random(&ingredients, &STAR_WEIGHTS, &mut rng);
+        assert_eq!(taste.flavor, "pink");
+        assert_eq!(taste.vetoed, vec![(1, 3)]);
+
+        let taste = Veto {
+            base: Additive::uniform(&ingredients, 5),
+            ..taste
+        };
+        assert_eq!(taste.rank(&donut(&[0, 0, 0])), 5);
+        assert_eq!(taste.rank(&donut(&[0, 3, 0])), 0);
+    }
+
+    #[test]
+    fn test_at_least_one_ranking() {
+        let ingredients = test_ingredients(&[3, 6, 7]);
+        let taste = AtLeastOne {
+            base: Additive::uniform(&ingredients, 5),
+            favorites: vec![(0, 1), (2, 5)],
+        };
+
+        assert_eq!(taste.rank(&donut(&[0, 0, 0])), AtLeastOne::THRESHOLD);
+        assert_eq!(taste.rank(&donut(&[1, 0, 0])), 5);
+        assert_eq!(taste.rank(&donut(&[0, 0, 5])), 5);
+    }
+
+    #[test]
+    fn test_random_tastes() {
+        let ingredients = test_ingredients(&[3, 6, 7]);
+        let donuts = all_donuts(&ingredients);
+        let mut rng = GameRng::new(42);
+
+        for kind in KINDS {
+            for _ in 0..20 {
+                let taste = Taste::random(kind, &ingredients, &STAR_WEIGHTS, &mut rng);
+                assert_eq!(taste.kind(), kind);
+                assert!(donuts.iter().all(|donut| taste.rank(donut) <= 5));
+            }
+        }
+    }
+
+    #[test]
+    fn test_seeded_tastes() {
+        let ingredients = test_ingredients(&[3, 6, 7]);
+        let mut rng = GameRng::new(42);
+        let mut same_rng = GameRng::new(42);
+
+        for kind in KINDS {
+            assert_eq!(
+                Taste::random(kind, &ingredients, &STAR_WEIGHTS, &mut rng),
+                Taste::random(kind, &ingredients, &STAR_WEIGHTS, &mut same_rng)
+            );
+        }
+    }
+
+    #[test]
+    fn test_hints() {
+        let mut ingredients = test_ingredients(&[2, 2, 2]);
+        let taste = Additive {
+            stars: vec![vec![5, 5], vec![1, 5], vec![5, 5]],
+        };
+        let offered = donut(&[0, 0, 0]);
+        assert_eq!(
+            Hint::find(&taste, &ingredients, &offered),
+            Some(Hint::WorstLayer(1))
+        );
+        assert_eq!(Hint::find(&taste, &ingredients, &donut(&[0, 1, 0])), None);
+
+        ingredients.layers[1].ingredients[0].flavors = vec!["sweet".to_string()];
+        assert_eq!(
+            Hint::find(&taste, &ingredients, &offered),
+            Some(Hint::TooMuch {
+                layer: 1,
+                flavor: "sweet".to_string()
+            })
+        );
+
+        // Nothing to improve one ingredient at a time
+        let taste = Additive {
+            stars: vec![vec![4, 1], vec![4, 1], vec![4, 1]],
+        };
+        assert_eq!(
+            Hint::find(&taste, &ingredients, &offered),
+            Some(Hint::BestLayer(0))
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+
+use bevy::core::CorePlugin;
+use bevy::prelude::*;
+use donut_tell_me::{
+    assets, component::*, difficulty::Difficulty, taste::Taste, AppState, DonutGamePlugin,
+};
+
+// Time passing with every `Harness::update`
+pub const FRAME_TIME: Duration = Duration::from_millis(100);
+
+// App running the game rules without rendering, driven by hand
+pub struct Harness {
+    pub app: App,
+    now: Instant,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_difficulty(Difficulty::default())
+    }
+
+    pub fn with_difficulty(difficulty: Difficulty) -> Self {
+        let mut app = App::new();
+        app.add_plugin(CorePlugin::default())
+            .insert_resource(Time::default())
+            .insert_resource(Input::<KeyCode>::default())
+            .insert_resource(GameRng::new(42))
+            .insert_resource(difficulty)
+            .add_startup_system(assets::init_headless)
+            .add_plugin(DonutGamePlugin)
+            .add_state(AppState::InGame);
+
+        let now = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(now);
+
+        let mut harness = Self { app, now };
+        harness.update();
+        harness
+    }
+
+    pub fn update(&mut self) {
+        self.now += FRAME_TIME;
+        self.app
+            .world
+            .resource_mut::<Time>()
+            .update_with_instant(self.now);
+        self.app.update();
+    }
+
+    pub fn wait(&mut self, seconds: f32) {
+        let frames = (seconds / FRAME_TIME.as_secs_f32()).ceil() as usize;
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    pub fn press(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<Input<KeyCode>>().press(key);
+        self.update();
+
+        let mut keys = self.app.world.resource_mut::<Input<KeyCode>>();
+        keys.release(key);
+        keys.clear();
+    }
+
+    pub fn click(&mut self, button: impl Component) {
+        let button = self
+            .app
+            .world
+            .spawn()
+            .insert(button)
+            .insert(Interaction::Clicked)
+            .id();
+        self.update();
+        self.app.world.despawn(button);
+    }
+
+    pub fn state(&self) -> AppState {
+        self.app
+            .world
+            .resource::<State<AppState>>()
+            .current()
+            .clone()
+    }
+
+    pub fn line(&self) -> Vec<Entity> {
+        self.app
+            .world
+            .resource::<Line>()
+            .0
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn current_customer(&mut self) -> Option<Entity> {
+        self.app
+            .world
+            .query_filtered::<Entity, With<CurrentCustomer>>()
+            .iter(&self.app.world)
+            .next()
+    }
+
+    pub fn cooking_donut(&mut self) -> Option<DonutLayers> {
+        self.app
+            .world
+            .query_filtered::<&DonutLayers, With<CookingDonut>>()
+            .iter(&self.app.world)
+            .next()
+            .cloned()
+    }
+
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.app.world.get::<T>(entity).is_some()
+    }
+
+    // Makes every customer in line love any donut
+    pub fn make_everyone_love_donuts(&mut self) {
+        let taste = Taste::all(self.app.world.resource::<Ingredients>());
+        let customers = self.line();
+        for customer in customers {
+            self.app.world.entity_mut(customer).insert(taste.clone());
+        }
+    }
+
+    // Makes every customer in line hate any donut
+    pub fn make_everyone_hate_donuts(&mut self) {
+        let taste = Taste::uniform(self.app.world.resource::<Ingredients>(), 1);
+        let customers = self.line();
+        for customer in customers {
+            self.app.world.entity_mut(customer).insert(taste.clone());
+        }
+    }
+
+    // Cooks a donut and offers it to the current customer, then waits for them to leave
+    pub fn serve(&mut self) {
+        self.press(KeyCode::N);
+        self.press(KeyCode::Return);
+        self.wait(2.5);
+    }
+}
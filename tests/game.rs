@@ -0,0 +1,466 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use donut_tell_me::{assets::BodyMetadata, component::*, difficulty::*, taste::*, AppState};
+
+#[test]
+fn test_line_fills_up() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+
+    let line = harness.line();
+    assert_eq!(line.len(), 4);
+    assert_eq!(harness.current_customer(), line.first().cloned());
+}
+
+#[test]
+fn test_same_seed_same_customers() {
+    // What every customer in line likes and looks like, front to back
+    fn customers(harness: &Harness) -> Vec<(Taste, Appearance)> {
+        let world = &harness.app.world;
+        harness
+            .line()
+            .into_iter()
+            .map(|customer| {
+                (
+                    world.get::<Taste>(customer).unwrap().clone(),
+                    *world.get::<Appearance>(customer).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    // Both harnesses are seeded the same
+    let mut first = Harness::new();
+    let mut second = Harness::new();
+    for harness in [&mut first, &mut second] {
+        harness.wait(1.);
+        harness.serve();
+        harness.serve();
+    }
+
+    assert_eq!(customers(&first), customers(&second));
+}
+
+#[test]
+fn test_cooking_with_keys() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.cooking_donut(), None);
+
+    harness.press(KeyCode::N);
+    assert_eq!(harness.cooking_donut(), Some(DonutLayers(vec![0, 0, 0])));
+
+    harness.press(KeyCode::W);
+    harness.press(KeyCode::A);
+    assert_eq!(harness.cooking_donut().map(|donut| donut.0[0]), Some(1));
+    assert_ne!(harness.cooking_donut().map(|donut| donut.0[1]), Some(0));
+}
+
+#[test]
+fn test_loved_donut_makes_a_regular() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    let customer = harness.current_customer().unwrap();
+    harness.press(KeyCode::N);
+    harness.press(KeyCode::Return);
+    assert!(harness.has::<Regular>(customer));
+    assert!(harness.has::<LeavingTimer>(customer));
+
+    harness.wait(2.5);
+    let next_customer = harness.current_customer().unwrap();
+    assert_ne!(next_customer, customer);
+    assert_eq!(harness.line().first(), Some(&next_customer));
+    assert!(harness.line().contains(&customer));
+}
+
+#[test]
+fn test_offer_button() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+
+    let customer = harness.current_customer().unwrap();
+    harness.click(NewDonutButton);
+    harness.click(OfferButton);
+    assert!(harness.has::<LeavingTimer>(customer));
+    assert_eq!(harness.current_customer(), Some(customer));
+
+    harness.wait(2.5);
+    assert!(!harness.has::<CurrentCustomer>(customer));
+}
+
+#[test]
+fn test_three_regulars_win() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    harness.serve();
+    harness.serve();
+    assert_eq!(harness.state(), AppState::InGame);
+
+    harness.serve();
+    assert_eq!(harness.state(), AppState::GameOver);
+}
+
+#[test]
+fn test_easy_difficulty() {
+    let mut harness = Harness::with_difficulty(Difficulty::preset(DifficultyLevel::Easy));
+    harness.wait(1.);
+    assert_eq!(harness.line().len(), 3);
+
+    harness.make_everyone_love_donuts();
+    harness.serve();
+    assert_eq!(harness.state(), AppState::InGame);
+
+    harness.serve();
+    assert_eq!(harness.state(), AppState::GameOver);
+}
+
+#[test]
+fn test_offers_are_recorded() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    let customer = harness.current_customer().unwrap();
+    let id = *harness.app.world.get::<CustomerId>(customer).unwrap();
+    harness.serve();
+
+    let history = harness.app.world.resource::<TransactionHistory>();
+    assert_eq!(history.0.len(), 1);
+    assert_eq!(history.0[0].customer, id);
+    assert_eq!(history.0[0].layers, DonutLayers(vec![0, 0, 0]));
+    assert_eq!(history.0[0].rank, 5);
+    assert_eq!(history.0[0].emotion, Emo::Love);
+    assert_eq!(history.0[0].revenue, 10);
+    assert_eq!(history.0[0].cost, 3);
+    assert_eq!(harness.app.world.resource::<Cash>().0, 17);
+}
+
+#[test]
+fn test_regulars_alone_do_not_win() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        cash_to_win: 100,
+        ..Default::default()
+    });
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    harness.serve();
+    harness.serve();
+    harness.serve();
+    assert_eq!(harness.state(), AppState::InGame);
+}
+
+#[test]
+fn test_pause_freezes_customers() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+
+    let customer = harness.current_customer().unwrap();
+    harness.press(KeyCode::N);
+    harness.press(KeyCode::Return);
+
+    harness
+        .app
+        .world
+        .resource_mut::<State<AppState>>()
+        .push(AppState::Paused)
+        .unwrap();
+    harness.wait(5.);
+    assert_eq!(harness.state(), AppState::Paused);
+    assert_eq!(harness.current_customer(), Some(customer));
+
+    harness
+        .app
+        .world
+        .resource_mut::<State<AppState>>()
+        .pop()
+        .unwrap();
+    harness.wait(2.5);
+    assert_eq!(harness.state(), AppState::InGame);
+    assert_ne!(harness.current_customer(), Some(customer));
+}
+
+#[test]
+fn test_two_sessions_back_to_back() {
+    let mut harness = Harness::new();
+
+    for _ in 0..2 {
+        harness.wait(1.);
+        assert_eq!(harness.line().len(), 4);
+        assert!(harness.app.world.get_resource::<LastUsedDonut>().is_none());
+        assert!(harness
+            .app
+            .world
+            .resource::<TransactionHistory>()
+            .0
+            .is_empty());
+        let first = harness.line()[0];
+        assert_eq!(
+            harness.app.world.get::<CustomerId>(first),
+            Some(&CustomerId(0))
+        );
+        assert!(!harness.has::<Regular>(first));
+
+        harness.make_everyone_love_donuts();
+        harness.serve();
+        harness.serve();
+        harness.serve();
+        assert_eq!(harness.state(), AppState::GameOver);
+        assert_eq!(
+            harness.app.world.resource::<TransactionHistory>().0.len(),
+            3
+        );
+
+        // Play again
+        harness
+            .app
+            .world
+            .resource_mut::<State<AppState>>()
+            .set(AppState::InGame)
+            .unwrap();
+        harness.update();
+    }
+}
+
+#[test]
+fn test_losing_customers_loses_the_game() {
+    let mut harness = Harness::new();
+    harness.wait(1.);
+    harness.make_everyone_hate_donuts();
+
+    // Heartbroken three times, a customer doesn't come back
+    let line = harness.line();
+    for _ in 0..line.len() * 2 {
+        harness.serve();
+    }
+    assert_eq!(harness.state(), AppState::InGame);
+    assert_eq!(harness.app.world.resource::<LostCustomers>().0, 0);
+
+    harness.serve();
+    assert!(harness.app.world.get_entity(line[0]).is_none());
+    assert!(!harness.line().contains(&line[0]));
+    assert_eq!(harness.app.world.resource::<LostCustomers>().0, 1);
+
+    harness.serve();
+    harness.serve();
+    assert_eq!(harness.state(), AppState::GameLost);
+}
+
+#[test]
+fn test_regulars_come_back_the_next_day() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        day_length: 10.,
+        ..Default::default()
+    });
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    let regular = harness.current_customer().unwrap();
+    harness.serve();
+    assert!(harness.has::<Regular>(regular));
+    let others: Vec<_> = harness
+        .line()
+        .into_iter()
+        .filter(|customer| *customer != regular)
+        .collect();
+
+    harness.wait(10.);
+    assert_eq!(harness.state(), AppState::DayOver);
+
+    harness
+        .app
+        .world
+        .resource_mut::<State<AppState>>()
+        .pop()
+        .unwrap();
+    harness.wait(1.);
+    assert_eq!(harness.state(), AppState::InGame);
+    assert_eq!(harness.app.world.resource::<Day>().number, 2);
+
+    let line = harness.line();
+    assert!(line.contains(&regular));
+    assert!(others.iter().all(|customer| !line.contains(customer)));
+    assert_eq!(line.len(), 5);
+}
+
+#[test]
+fn test_last_day_ends_the_game() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        day_length: 5.,
+        days: 1,
+        ..Default::default()
+    });
+    harness.wait(6.);
+    assert_eq!(harness.state(), AppState::GameLost);
+}
+
+#[test]
+fn test_winning_on_the_last_tick() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        day_length: 5.,
+        days: 1,
+        regulars_to_win: 1,
+        cash_to_win: 0,
+        ..Default::default()
+    });
+    harness.wait(4.);
+    assert_eq!(harness.state(), AppState::InGame);
+
+    // The clock runs out in the very frame the customer becomes a regular
+    let customer = harness.current_customer().unwrap();
+    harness.app.world.entity_mut(customer).insert(Regular);
+    let mut day = harness.app.world.resource_mut::<Day>();
+    let almost_over = day.clock.duration() - common::FRAME_TIME / 2;
+    day.clock.set_elapsed(almost_over);
+
+    harness.update();
+    assert_eq!(harness.state(), AppState::GameOver);
+}
+
+#[test]
+fn test_regular_wants_something_new() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        regulars_to_win: 10,
+        something_new_chance: 1.,
+        ..Default::default()
+    });
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    let regular = harness.current_customer().unwrap();
+    harness.serve();
+    assert!(harness.has::<Regular>(regular));
+    assert_eq!(
+        harness.app.world.get::<Loyalty>(regular),
+        Some(&Loyalty(Loyalty::MAX))
+    );
+
+    // Everyone else in line gets served before the regular comes back
+    while harness.current_customer() != Some(regular) {
+        harness.serve();
+    }
+    assert_eq!(
+        harness.app.world.get::<Mood>(regular),
+        Some(&Mood::SomethingNew)
+    );
+
+    // The same donut again
+    harness.serve();
+    let history = harness.app.world.resource::<TransactionHistory>();
+    assert_eq!(history.0.last().unwrap().emotion, Emo::Sad);
+    assert!(!harness.has::<Mood>(regular));
+
+    // One bad donut isn't enough to lose a regular
+    assert!(harness.has::<Regular>(regular));
+    assert_eq!(
+        harness.app.world.get::<Loyalty>(regular),
+        Some(&Loyalty(Loyalty::MAX - 1))
+    );
+}
+
+#[test]
+fn test_hints_run_out() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        hints_per_day: 1,
+        ..Default::default()
+    });
+    harness.wait(1.);
+
+    // Everything but the plain base is perfect
+    let mut taste = Additive::uniform(harness.app.world.resource::<Ingredients>(), 5);
+    taste.stars[0][0] = 1;
+    for customer in harness.line() {
+        harness
+            .app
+            .world
+            .entity_mut(customer)
+            .insert(Taste::Additive(taste.clone()));
+    }
+
+    harness.serve();
+    harness.serve();
+
+    let history = harness.app.world.resource::<TransactionHistory>();
+    assert_eq!(history.0[0].emotion, Emo::Happy);
+    assert_eq!(
+        history.0[0].hint,
+        Some(Hint::TooMuch {
+            layer: 0,
+            flavor: "vanilla".to_string()
+        })
+    );
+    assert_eq!(history.0[1].hint, None);
+    assert_eq!(harness.app.world.resource::<HintsLeft>().0, 0);
+}
+
+#[test]
+fn test_no_hint_on_a_repeat() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        regulars_to_win: 10,
+        something_new_chance: 1.,
+        ..Default::default()
+    });
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    let regular = harness.current_customer().unwrap();
+    harness.serve();
+    while harness.current_customer() != Some(regular) {
+        harness.serve();
+    }
+
+    // The same donut again, now one a hint could improve
+    let mut taste = Additive::uniform(harness.app.world.resource::<Ingredients>(), 5);
+    taste.stars[0][0] = 1;
+    harness
+        .app
+        .world
+        .entity_mut(regular)
+        .insert(Taste::Additive(taste));
+    harness.serve();
+
+    let history = harness.app.world.resource::<TransactionHistory>();
+    let repeat = history.0.last().unwrap();
+    assert_eq!(repeat.emotion, Emo::Angry);
+    assert_eq!(repeat.hint, None);
+}
+
+#[test]
+fn test_customers_look_the_same_the_next_day() {
+    let mut harness = Harness::with_difficulty(Difficulty {
+        day_length: 5.,
+        ..Default::default()
+    });
+    harness.wait(1.);
+    harness.make_everyone_love_donuts();
+
+    let bodies = harness.app.world.resource::<BodyMetadata>();
+    assert_eq!(bodies.tints.len(), 8);
+    assert_eq!(bodies.shirts.len(), 64);
+    assert_eq!(bodies.pants.len(), 48);
+
+    let regular = harness.current_customer().unwrap();
+    let appearance = *harness.app.world.get::<Appearance>(regular).unwrap();
+    assert!(appearance.body.is_some());
+
+    harness.serve();
+    harness.wait(5.);
+    harness
+        .app
+        .world
+        .resource_mut::<State<AppState>>()
+        .pop()
+        .unwrap();
+    harness.update();
+
+    assert_eq!(harness.app.world.resource::<Day>().number, 2);
+    assert_eq!(
+        harness.app.world.get::<Appearance>(regular),
+        Some(&appearance)
+    );
+}